@@ -0,0 +1,1862 @@
+use crate::eval::*;
+use crate::game::*;
+use crate::geometry::*;
+use crate::protocol::*;
+use crate::rules::*;
+use rand::{Rng, SeedableRng};
+use std::time::Instant;
+#[cfg(test)]
+use {crate::tracking::*, std::time::Duration};
+
+// =======================================================================
+// IA
+// =======================================================================
+// Entropy of a yes/no answer whose "yes" probability is p
+pub fn binary_entropy(p: f32) -> f32 {
+    if p <= 0.0 || p >= 1.0 {
+        return 0.0;
+    }
+    -(p * p.log2() + (1.0 - p) * (1.0 - p).log2())
+}
+
+// Torpedo target a player tracking its opponent with candidates would pick:
+// the water cell with the most expected damage
+pub fn best_guess_shot(map: &Map, candidates: &Map) -> Option<Pos> {
+    let mut hits = vec![0; map.h * map.w];
+    for y in 0..map.h {
+        for x in 0..map.w {
+            if !candidates.get(y, x) {
+                continue;
+            }
+            let pos = Pos { y, x };
+            for ty in y.saturating_sub(1)..std::cmp::min(y + 2, map.h) {
+                for tx in x.saturating_sub(1)..std::cmp::min(x + 2, map.w) {
+                    hits[ty * map.w + tx] += blast_damage(&Pos { y: ty, x: tx }, &pos);
+                }
+            }
+        }
+    }
+    (0..map.h)
+        .flat_map(|y| (0..map.w).map(move |x| Pos { y, x }))
+        .filter(|pos| !map.get(pos.y, pos.x) && hits[pos.y * map.w + pos.x] > 0)
+        .max_by_key(|pos| hits[pos.y * map.w + pos.x])
+}
+
+// -----------------------------------------------------------------------
+// ChargePriorities
+// -----------------------------------------------------------------------
+// Values every charge priority may take
+pub const CHARGE_PRIORITY_RANGE: (f32, f32) = (0.0, 5.0);
+
+// Weight of each system need when choosing what a MOVE charges, set in the
+// AI configuration as "charge.<system>"
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChargePriorities {
+    pub torpedo: f32,
+    pub sonar: f32,
+    pub silence: f32,
+    pub mine: f32,
+}
+
+impl ChargePriorities {
+    pub fn new() -> Self {
+        Self {
+            torpedo: 1.0,
+            sonar: 0.6,
+            silence: 0.8,
+            mine: 0.4,
+        }
+    }
+
+    pub fn set(&mut self, key: &str, value: f32) -> Result<(), String> {
+        match key {
+            "torpedo" => self.torpedo = value,
+            "sonar" => self.sonar = value,
+            "silence" => self.silence = value,
+            "mine" => self.mine = value,
+            key => return Err(format!("Unknown charge priority {}", key)),
+        }
+        Ok(())
+    }
+
+    pub fn values(&self) -> Vec<(&'static str, f32)> {
+        vec![
+            ("torpedo", self.torpedo),
+            ("sonar", self.sonar),
+            ("silence", self.silence),
+            ("mine", self.mine),
+        ]
+    }
+}
+
+impl Default for ChargePriorities {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// -----------------------------------------------------------------------
+// AiConfig
+// -----------------------------------------------------------------------
+// Every heuristic parameter, with the defaults we submit. The file named by
+// OOC_AI_CONFIG can override them with "name = value" lines, the charge
+// priorities and evaluation weights being named "charge.<system>" and
+// "eval.<feature>". Values out of the range of their parameter are rejected.
+macro_rules! ai_config {
+    ($($name:ident: $type:ty = $default:literal in $min:literal..=$max:literal,)*) => {
+        #[derive(Debug, Clone, PartialEq)]
+        pub struct AiConfig {
+            $(pub $name: $type,)*
+            pub charge_priorities: ChargePriorities,
+            pub eval_weights: EvalWeights,
+        }
+
+        impl AiConfig {
+            pub fn new() -> Self {
+                Self {
+                    $($name: $default,)*
+                    charge_priorities: ChargePriorities::new(),
+                    eval_weights: EvalWeights::new(),
+                }
+            }
+
+            // Valid values of the parameter, the charge priorities and the
+            // evaluation weights sharing theirs
+            pub fn range(key: &str) -> Result<(f32, f32), String> {
+                match key {
+                    $(stringify!($name) => Ok(($min as f32, $max as f32)),)*
+                    key => match key.split_once('.') {
+                        Some(("charge", _)) => Ok(CHARGE_PRIORITY_RANGE),
+                        Some(("eval", _)) => Ok(EVAL_WEIGHT_RANGE),
+                        _ => Err(format!("Unknown AI parameter {}", key)),
+                    },
+                }
+            }
+
+            // Integer parameters only take whole values
+            pub fn set(&mut self, key: &str, value: f32) -> Result<(), String> {
+                let (min, max) = Self::range(key)?;
+                if !(min..=max).contains(&value) {
+                    return Err(format!("{} out of [{}, {}]: {}", key, min, max, value));
+                }
+                match key {
+                    $(stringify!($name) => {
+                        if value as $type as f32 != value {
+                            return Err(format!("Bad value for {}: {}", key, value));
+                        }
+                        self.$name = value as $type;
+                    })*
+                    key => match key.split_once('.') {
+                        Some(("charge", key)) => self.charge_priorities.set(key, value)?,
+                        Some(("eval", key)) => self.eval_weights.set(key, value)?,
+                        _ => return Err(format!("Unknown AI parameter {}", key)),
+                    },
+                }
+                Ok(())
+            }
+
+            pub fn values(&self) -> Vec<(String, f32)> {
+                let mut ret = vec![$((stringify!($name).to_string(), self.$name as f32),)*];
+                for (key, value) in self.charge_priorities.values() {
+                    ret.push((format!("charge.{}", key), value));
+                }
+                for (key, value) in self.eval_weights.values() {
+                    ret.push((format!("eval.{}", key), value));
+                }
+                ret
+            }
+        }
+    };
+}
+
+ai_config! {
+    // Minimum expected information gain, in bits, for a sonar to be worth it
+    sonar_min_gain: f32 = 0.7 in 0.0..=4.0,
+    // Below this many candidate cells the opponent is considered localised
+    torpedo_charge_max_candidates: usize = 4 in 1..=50,
+    // Expected damage above which a mine is triggered or a torpedo fired
+    mine_trigger_min_ev: f32 = 0.5 in 0.0..=2.0,
+    torpedo_min_ev: f32 = 0.5 in 0.0..=2.0,
+    // Stances: we defend ourselves at that life or below, and go for the kill
+    // when the opponent is that low and not healthier than us
+    defensive_life: i32 = 2 in -1..=6,
+    aggressive_opp_life: i32 = 2 in -1..=6,
+    // Factor applied to the expected damage thresholds when aggressive, and
+    // to the charge need of the favoured system
+    aggressive_ev_factor: f32 = 0.5 in 0.0..=1.0,
+    stance_charge_factor: f32 = 2.0 in 1.0..=5.0,
+    // Turns left from which the game is played for the life count at the cap
+    endgame_turns: usize = 30 in 0..=300,
+    // When closing in on the opponent, the route must still be that long
+    engage_min_path: usize = 8 in 0..=50,
+    // Cost of each life point our own blasts take from us, lowered when we
+    // must trade blows to catch up
+    turn_self_damage_weight: f32 = 1.5 in 0.0..=5.0,
+    engage_self_damage_weight: f32 = 1.0 in 0.0..=5.0,
+    // Number of opponent moves a dropped mine is expected to wait for
+    mine_cover_depth: usize = 2 in 0..=5,
+    // Depth and node budget of the longest route search, for each direction
+    path_search_depth: usize = 40 in 1..=225,
+    path_search_budget: usize = 4000 in 0..=100_000,
+    // Moves equal on everything else go the most preferred way
+    move_preference_n: usize = 1 in 0..=3,
+    move_preference_e: usize = 0 in 0..=3,
+    move_preference_s: usize = 3 in 0..=3,
+    move_preference_w: usize = 2 in 0..=3,
+    // Surfacing before being stuck is only worth it when the route ahead is
+    // this short, the area it gives back is this many times larger and we can
+    // spare the life point
+    surface_cheap_path: usize = 3 in 0..=20,
+    surface_cheap_gain: usize = 4 in 1..=20,
+    surface_min_life: i32 = 3 in 1..=6,
+    // We are exposed when the opponent has at most this many cells left for us
+    silence_exposed_max: usize = 10 in 0..=225,
+    // Route length a silence must leave us with, and the search budget used to
+    // check it
+    silence_min_path: usize = 6 in 0..=50,
+    silence_search_budget: usize = 500 in 0..=100_000,
+    // Expected mine damage we accept to move into at full life, scaled down
+    // with our remaining life
+    mine_danger_max: f32 = 0.5 in 0.0..=2.0,
+    // Threats are only dodged into directions leaving a route that long, a
+    // dead end being worse than any of them
+    threat_min_path: usize = 6 in 1..=50,
+    // Deception: value of each bit of opponent uncertainty about our position
+    // and of each damage point its best guess shot would deal us, and cost of
+    // spending a charged system on a decoy
+    deception_bit_value: f32 = 0.3 in 0.0..=2.0,
+    deception_exposure_value: f32 = 0.5 in 0.0..=2.0,
+    decoy_torpedo_cost: f32 = 0.5 in 0.0..=5.0,
+    decoy_silence_cost: f32 = 0.8 in 0.0..=5.0,
+    // Search: maximum plies played by each side after our turn, deepened one
+    // at a time while the clock allows, and opponent positions sampled at each
+    // depth
+    search_max_plies: usize = 4 in 0..=20,
+    search_samples_per_depth: usize = 32 in 1..=1000,
+    // Weight of the heuristic turn score in the search, to break ties
+    // between turns with the same outcome
+    search_prior_weight: f32 = 0.1 in 0.0..=1.0,
+    // Share of the turn time the move planner and the search may use, the
+    // rest being kept for the deception, sonar and mine planners
+    search_time_share: f32 = 0.8 in 0.1..=1.0,
+    // Weight of the opponent direction habits against the exits of each move
+    // in the rollouts
+    rollout_dir_bias_weight: f32 = 0.5 in 0.0..=2.0,
+    // Start point scoring weights
+    start_area_weight: f32 = 4.0 in 0.0..=10.0,
+    start_opp_dist_weight: f32 = 1.0 in 0.0..=10.0,
+    start_center_weight: f32 = 0.5 in 0.0..=10.0,
+    start_ambiguity_weight: f32 = 2.0 in 0.0..=10.0,
+    // Radius of the neighbourhood two cells must share to look alike
+    start_ambiguity_radius: usize = 2 in 0..=5,
+    // The start is drawn among that many best scored cells
+    start_nb_choices: usize = 5 in 1..=50,
+}
+
+impl AiConfig {
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let mut ret = Self::new();
+        for (key, value) in parse_key_values(s)? {
+            ret.set(&key, value)?;
+        }
+        Ok(ret)
+    }
+
+    pub fn load(path: &str) -> Result<Self, String> {
+        let s = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+        Self::parse(&s)
+    }
+
+    pub fn move_preference(&self, dir: &Direction) -> usize {
+        match dir {
+            Direction::N => self.move_preference_n,
+            Direction::E => self.move_preference_e,
+            Direction::S => self.move_preference_s,
+            Direction::W => self.move_preference_w,
+        }
+    }
+}
+
+impl Default for AiConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Written in the format parse reads
+impl std::fmt::Display for AiConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (key, value) in self.values() {
+            writeln!(f, "{} = {}", key, value)?;
+        }
+        Ok(())
+    }
+}
+
+// -----------------------------------------------------------------------
+// Turn composition
+// -----------------------------------------------------------------------
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Timing {
+    Never,
+    Before,
+    After,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TurnMove {
+    Move(Direction),
+    Silence,
+    MoveSilence(Direction),
+    Surface,
+}
+
+// -----------------------------------------------------------------------
+// Stance
+// -----------------------------------------------------------------------
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Stance {
+    Normal,
+    // Low on life: keep out of torpedo range, hide and never surface to death
+    Defensive,
+    // The opponent is low on life: charge and fire more eagerly
+    Aggressive,
+    // Ahead on life at the end: stay out of reach and never fire
+    Hide,
+    // Behind on life at the end: close in and accept trades
+    Engage,
+}
+
+impl Stance {
+    pub fn is_defensive(self) -> bool {
+        self == Stance::Defensive || self == Stance::Hide
+    }
+
+    pub fn is_aggressive(self) -> bool {
+        self == Stance::Aggressive || self == Stance::Engage
+    }
+}
+
+// -----------------------------------------------------------------------
+// Ai
+// -----------------------------------------------------------------------
+pub struct Ai {
+    pub dir: Option<Direction>,
+    pub stance: Stance,
+    pub surfaced: bool,
+    pub config: AiConfig,
+    pub rng: rand::rngs::StdRng,
+}
+
+impl Ai {
+    pub fn new() -> Self {
+        // OOC_SEED makes a run reproducible
+        let seed = match std::env::var("OOC_SEED").map(|seed| seed.parse()) {
+            Ok(Ok(seed)) => seed,
+            Ok(Err(e)) => {
+                log!(Parse, Error, "Bad OOC_SEED, using a random seed: {}", e);
+                rand::random()
+            }
+            Err(_) => rand::random(),
+        };
+        Self::with_seed(seed)
+    }
+
+    pub fn with_seed(seed: u64) -> Self {
+        let config = match std::env::var("OOC_AI_CONFIG").map(|path| AiConfig::load(&path)) {
+            Ok(Ok(config)) => config,
+            Ok(Err(e)) => {
+                log!(Parse, Error, "Bad OOC_AI_CONFIG, using the defaults: {}", e);
+                AiConfig::new()
+            }
+            Err(_) => AiConfig::new(),
+        };
+        Self::with_config(seed, config)
+    }
+
+    pub fn with_config(seed: u64, config: AiConfig) -> Self {
+        Self {
+            dir: None,
+            stance: Stance::Normal,
+            surfaced: false,
+            config,
+            rng: rand::rngs::StdRng::seed_from_u64(seed),
+        }
+    }
+}
+
+impl Default for Ai {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Islands and borders around pos, used to spot cells that look alike
+pub fn neighbourhood_signature(map: &Map, pos: &Pos, radius: usize) -> Vec<bool> {
+    let radius = radius as isize;
+    let mut ret = vec![];
+    for dy in -radius..=radius {
+        for dx in -radius..=radius {
+            let y = pos.y as isize + dy;
+            let x = pos.x as isize + dx;
+            let blocked = y < 0
+                || x < 0
+                || y >= map.h as isize
+                || x >= map.w as isize
+                || map.get(y as usize, x as usize);
+            ret.push(blocked);
+        }
+    }
+    ret
+}
+
+impl Ai {
+    // Every start is scored on:
+    // - the size of its water area, so that the route can be long,
+    // - the mean distance to the other water cells, where the opponent is
+    //   likely to start,
+    // - its distance to the map center, as edges limit the escape routes,
+    // - the number of cells looking the same, as they make our route
+    //   ambiguous for the opponent.
+    pub fn score_start_points(&self, map: &Map) -> Vec<(Pos, f32)> {
+        let water: Vec<_> = (0..map.h)
+            .flat_map(|y| (0..map.w).map(move |x| Pos { y, x }))
+            .filter(|pos| !map.get(pos.y, pos.x))
+            .collect();
+        let nb_water = water.len() as f32;
+        let max_dist = (map.h + map.w) as f32;
+        let center = Pos {
+            y: map.h / 2,
+            x: map.w / 2,
+        };
+        let signatures: Vec<_> = water
+            .iter()
+            .map(|pos| neighbourhood_signature(map, pos, self.config.start_ambiguity_radius))
+            .collect();
+        water
+            .iter()
+            .enumerate()
+            .map(|(i, pos)| {
+                let area = (1 + map.flood_size(pos)) as f32 / nb_water;
+                let opp_dist = water.iter().map(|opp| pos.dist(opp)).sum::<usize>() as f32
+                    / nb_water
+                    / max_dist;
+                let center = 1.0 - pos.dist(&center) as f32 / max_dist;
+                let ambiguity = signatures
+                    .iter()
+                    .filter(|signature| **signature == signatures[i])
+                    .count() as f32
+                    / nb_water;
+                let score = self.config.start_area_weight * area
+                    + self.config.start_opp_dist_weight * opp_dist
+                    + self.config.start_center_weight * center
+                    + self.config.start_ambiguity_weight * ambiguity;
+                (*pos, score)
+            })
+            .collect()
+    }
+
+    pub fn choose_start(&mut self, map: &Map) -> Pos {
+        let mut scores = self.score_start_points(map);
+        scores.sort_by(|(_, a), (_, b)| b.partial_cmp(a).unwrap());
+        scores.truncate(self.config.start_nb_choices);
+        if scores.is_empty() {
+            map.rand_false_pos(&mut self.rng).unwrap()
+        } else {
+            scores[self.rng.gen_range(0, scores.len())].0
+        }
+    }
+
+    pub fn select_start_point(&mut self, game: &mut Game) {
+        let start_pos = self.choose_start(&game.map);
+        game.start_at(start_pos.y, start_pos.x);
+    }
+}
+impl Ai {
+    // With uniform candidates, the expected entropy reduction of a sonar is the
+    // entropy of its answer
+    pub fn best_sonar_sector(&self, game: &Game) -> Option<(usize, f32)> {
+        let candidates = game.opp.candidates(&game.map);
+        let total = candidates.nb_true();
+        if total <= 1 {
+            return None;
+        }
+        (1..=NB_SECTORS)
+            .map(|sector| {
+                let inside = candidates.nb_true_and(&game.map.sector_mask(sector));
+                (sector, binary_entropy(inside as f32 / total as f32))
+            })
+            .max_by(|(_, max), (_, v)| max.partial_cmp(v).unwrap())
+    }
+
+    pub fn plan_sonar(&mut self, game: &mut Game) {
+        if game.me.sonar > 0 {
+            return;
+        }
+        if let Some((sector, gain)) = self.best_sonar_sector(game) {
+            log!(Planner, Info, "Sonar sector {}, gain {}", sector, gain);
+            if gain >= self.config.sonar_min_gain {
+                game.sonar(sector).unwrap();
+            }
+        }
+    }
+
+    // Each system need is between 0 and 1:
+    // - torpedo when the opponent is localised,
+    // - silence when we are exposed,
+    // - sonar as its information gain,
+    // - mine when the opponent is too spread for a torpedo.
+    // Systems already charged are not worth charging.
+    pub fn plan_charge(&self, game: &Game) -> System {
+        let nb_opp = std::cmp::max(1, game.opp.candidates(&game.map).nb_true());
+        let nb_seen = std::cmp::max(1, game.me.seen.candidates(game.map.h, game.map.w).nb_true());
+        let torpedo_need =
+            (self.config.torpedo_charge_max_candidates as f32 / nb_opp as f32).min(1.0);
+        let silence_need = (self.config.silence_exposed_max as f32 / nb_seen as f32).min(1.0);
+        let sonar_need = self
+            .best_sonar_sector(game)
+            .map(|(_, gain)| gain)
+            .unwrap_or(0.0);
+        let mine_need = 1.0 - torpedo_need;
+
+        let (torpedo_need, silence_need) = match self.stance {
+            Stance::Normal => (torpedo_need, silence_need),
+            Stance::Defensive | Stance::Hide => (
+                torpedo_need,
+                self.config.stance_charge_factor * silence_need,
+            ),
+            Stance::Aggressive | Stance::Engage => (
+                self.config.stance_charge_factor * torpedo_need,
+                silence_need,
+            ),
+        };
+        let priorities = &self.config.charge_priorities;
+        let needs = [
+            (
+                System::Torpedo,
+                game.me.torpedo,
+                priorities.torpedo * torpedo_need,
+            ),
+            (
+                System::Silence,
+                game.me.silence,
+                priorities.silence * silence_need,
+            ),
+            (System::Sonar, game.me.sonar, priorities.sonar * sonar_need),
+            (System::Mine, game.me.mine, priorities.mine * mine_need),
+        ];
+        log!(Planner, Trace, "Charge needs: {:?}", needs);
+        needs
+            .iter()
+            .filter(|(_, cooldown, _)| *cooldown > 0)
+            .max_by(|(_, _, max), (_, _, v)| max.partial_cmp(v).unwrap())
+            .map(|(system, _, _)| *system)
+            .unwrap_or(System::Torpedo)
+    }
+}
+impl Ai {
+    // Expected damage dealt by a blast centered on pos, candidates being equally
+    // likely
+    pub fn blast_ev(game: &Game, weights: &[f32], pos: &Pos) -> f32 {
+        let total: f32 = weights.iter().sum();
+        if total <= 0.0 {
+            return 0.0;
+        }
+        let mut hits = 0.0;
+        for y in pos.y.saturating_sub(1)..std::cmp::min(pos.y + 2, game.map.h) {
+            for x in pos.x.saturating_sub(1)..std::cmp::min(pos.x + 2, game.map.w) {
+                hits += blast_damage(pos, &Pos { y, x }) as f32 * weights[y * game.map.w + x];
+            }
+        }
+        hits / total
+    }
+
+    pub fn best_trigger(&self, game: &Game, weights: &[f32]) -> Option<(Pos, f32)> {
+        game.me
+            .mines
+            .iter()
+            .filter(|mine| mine.square_dist(&game.me.pos) > 1)
+            .filter(|mine| game.last_turn.mine != Some(**mine))
+            .map(|mine| (*mine, Self::blast_ev(game, weights, mine)))
+            .max_by(|(_, max), (_, v)| max.partial_cmp(v).unwrap())
+    }
+
+    pub fn self_damage_weight(&self) -> f32 {
+        match self.stance {
+            Stance::Engage => self.config.engage_self_damage_weight,
+            _ => self.config.turn_self_damage_weight,
+        }
+    }
+
+    // Our own damage is taken off the expected damage, and lethal shots are
+    // never considered
+    pub fn best_torpedo(&self, game: &Game, weights: &[f32]) -> Option<(Pos, f32)> {
+        if game.me.torpedo > 0 {
+            return None;
+        }
+        let self_damage_weight = self.self_damage_weight();
+        let dists = game.map.distances_from(&game.me.pos, 4);
+        (0..game.map.h)
+            .flat_map(|y| (0..game.map.w).map(move |x| Pos { y, x }))
+            .filter(|pos| dists[pos.y * game.map.w + pos.x].is_some())
+            .filter(|pos| blast_damage(pos, &game.me.pos) < game.me.life)
+            .map(|pos| {
+                let self_damage = blast_damage(&pos, &game.me.pos) as f32;
+                let ev = Self::blast_ev(game, weights, &pos) - self_damage_weight * self_damage;
+                (pos, ev)
+            })
+            .max_by(|(_, max), (_, v)| max.partial_cmp(v).unwrap())
+    }
+
+    // Mines are dropped where the opponent is likely to pass in the next few
+    // turns
+    pub fn plan_mine(&mut self, game: &mut Game) {
+        if game.me.mine > 0 {
+            return;
+        }
+        let mut reach = game.opp.candidates(&game.map);
+        reach.expand(self.config.mine_cover_depth);
+        reach.eq_and_not(&game.map);
+        let dirs = [Direction::E, Direction::N, Direction::W, Direction::S];
+        let best = dirs
+            .iter()
+            .filter_map(|dir| {
+                let pos = dir.apply(&game.me.pos).ok()?;
+                if pos.x >= game.map.w || pos.y >= game.map.h || game.map.get(pos.y, pos.x) {
+                    return None;
+                }
+                if game.me.mines.contains(&pos) {
+                    return None;
+                }
+                Some((*dir, reach.nb_true_and(&game.map.square(&pos))))
+            })
+            .max_by(|(_, max), (_, v)| max.cmp(v));
+        if let Some((dir, coverage)) = best {
+            if coverage > 0 {
+                game.mine(&dir).unwrap();
+            }
+        }
+    }
+}
+impl Ai {
+    // Returns the longest route found and the usable area behind the move
+    pub fn score_route(&self, game: &Game, dir: &Direction, budget: usize) -> (usize, usize) {
+        let next_pos = dir.apply(&game.me.pos).unwrap();
+        let mut map = game.me.forbidden_map.clone();
+        map.set(next_pos.y, next_pos.x, true);
+        let area = 1 + map.usable_area(&next_pos);
+        let mut budget = budget;
+        let path = 1 + map.longest_path(&next_pos, self.config.path_search_depth - 1, &mut budget);
+        (path, area)
+    }
+
+    pub fn surface_is_cheap(&self, game: &Game, path: usize, area: usize) -> bool {
+        if path > self.config.surface_cheap_path || game.me.life < self.config.surface_min_life {
+            return false;
+        }
+        let mut map = game.map.clone();
+        map.set(game.me.pos.y, game.me.pos.x, true);
+        map.usable_area(&game.me.pos) >= self.config.surface_cheap_gain * area
+    }
+
+    // Directions leading into likely opponent mine blasts are dropped, unless
+    // there is nothing else
+    pub fn avoid_mines(&self, game: &Game, dirs: Vec<Direction>) -> Vec<Direction> {
+        let danger = game.opp.mines.danger();
+        let max_danger = self.config.mine_danger_max * game.me.life as f32 / MAX_LIFE as f32;
+        let safe_dirs: Vec<_> = dirs
+            .iter()
+            .filter(|d| {
+                let next_pos = d.apply(&game.me.pos).unwrap();
+                game.opp.mines.blast_danger(&danger, &next_pos) <= max_danger
+            })
+            .cloned()
+            .collect();
+        self.dodge(game, dirs, safe_dirs)
+    }
+
+    // Whether the move leaves a route of at least len cells
+    pub fn has_route(&self, game: &Game, dir: &Direction, len: usize) -> bool {
+        let next_pos = dir.apply(&game.me.pos).unwrap();
+        let mut map = game.me.forbidden_map.clone();
+        map.set(next_pos.y, next_pos.x, true);
+        let mut budget = self.config.silence_search_budget;
+        1 + map.longest_path(&next_pos, len.saturating_sub(1), &mut budget) >= len
+    }
+
+    // Directions a threat filter keeps out of dirs: the safe ones when one of
+    // them leads somewhere, else the ones leading somewhere, else all of them
+    pub fn dodge(
+        &self,
+        game: &Game,
+        dirs: Vec<Direction>,
+        safe_dirs: Vec<Direction>,
+    ) -> Vec<Direction> {
+        let roomy = |d: &Direction| self.has_route(game, d, self.config.threat_min_path);
+        if safe_dirs.iter().any(roomy) {
+            return safe_dirs;
+        }
+        let roomy_dirs: Vec<_> = dirs.iter().filter(|d| roomy(d)).cloned().collect();
+        if roomy_dirs.is_empty() {
+            dirs
+        } else {
+            roomy_dirs
+        }
+    }
+
+    pub fn stance(&self, game: &Game) -> Stance {
+        if game.turns_left() <= self.config.endgame_turns && game.me.life != game.opp.life {
+            if game.me.life > game.opp.life {
+                Stance::Hide
+            } else {
+                Stance::Engage
+            }
+        } else if game.opp.life <= self.config.aggressive_opp_life && game.opp.life <= game.me.life
+        {
+            Stance::Aggressive
+        } else if game.me.life <= self.config.defensive_life {
+            Stance::Defensive
+        } else {
+            Stance::Normal
+        }
+    }
+
+    pub fn min_ev(&self, min_ev: f32) -> f32 {
+        if self.stance.is_aggressive() {
+            self.config.aggressive_ev_factor * min_ev
+        } else {
+            min_ev
+        }
+    }
+
+    // When defending, directions the opponent may torpedo are dropped unless
+    // there is nothing else
+    pub fn avoid_torpedoes(&self, game: &Game, dirs: Vec<Direction>) -> Vec<Direction> {
+        if !self.stance.is_defensive() {
+            return dirs;
+        }
+        let threat = game.torpedo_threat();
+        let safe_dirs: Vec<_> = dirs
+            .iter()
+            .filter(|d| {
+                let next_pos = d.apply(&game.me.pos).unwrap();
+                !threat.get(next_pos.y, next_pos.x)
+            })
+            .cloned()
+            .collect();
+        self.dodge(game, dirs, safe_dirs)
+    }
+
+    // Manhattan distance to the closest opponent candidate
+    pub fn opp_dist(candidates: &Map, pos: &Pos) -> usize {
+        (0..candidates.h)
+            .flat_map(|y| (0..candidates.w).map(move |x| Pos { y, x }))
+            .filter(|cell| candidates.get(cell.y, cell.x))
+            .map(|cell| cell.dist(pos))
+            .min()
+            .unwrap_or(0)
+    }
+
+    // Legal directions, but those into likely mine blasts or, when defending,
+    // torpedo range
+    pub fn safe_dirs(&self, game: &Game) -> Vec<Direction> {
+        let dirs = vec![Direction::N, Direction::E, Direction::S, Direction::W];
+        let dirs: Vec<_> = dirs
+            .into_iter()
+            .filter(|d| game.can_move_to(d).is_ok())
+            .collect();
+        let dirs = self.avoid_mines(game, dirs);
+        self.avoid_torpedoes(game, dirs)
+    }
+
+    pub fn plan_move(&mut self, game: &mut Game) -> Option<Direction> {
+        let good_dirs = self.safe_dirs(game);
+        if good_dirs.is_empty() {
+            return None;
+        }
+        log!(Planner, Debug, "Possible directions: {:?}", good_dirs);
+
+        // Longest route first, then largest area, then keep the heading, then
+        // the preferred direction. When engaging, getting closer comes first as
+        // long as the route is long enough.
+        let candidates = game.opp.candidates(&game.map);
+        let (min_path, engage) = match self.stance {
+            Stance::Engage => (self.config.engage_min_path, true),
+            _ => (usize::MAX, false),
+        };
+        // Out of time, the routes left get the smaller silence search budget
+        let deadline = game.clock.deadline_at(self.config.search_time_share);
+        let (dir, path, area, _) = good_dirs
+            .iter()
+            .map(|dir| {
+                let budget = match Instant::now() < deadline {
+                    true => self.config.path_search_budget,
+                    false => self.config.silence_search_budget,
+                };
+                let (path, area) = self.score_route(game, dir, budget);
+                let next_pos = dir.apply(&game.me.pos).unwrap();
+                let opp_dist = match engage {
+                    true => Self::opp_dist(&candidates, &next_pos),
+                    false => 0,
+                };
+                log!(
+                    Planner,
+                    Debug,
+                    "DIR {}, path {}, area {}, opp dist {}",
+                    dir,
+                    path,
+                    area,
+                    opp_dist
+                );
+                (*dir, path, area, opp_dist)
+            })
+            .max_by_key(|(dir, path, area, opp_dist)| {
+                (
+                    (*path).min(min_path),
+                    std::cmp::Reverse(*opp_dist),
+                    *path,
+                    *area,
+                    self.dir == Some(*dir),
+                    self.config.move_preference(dir),
+                )
+            })
+            .unwrap();
+        if !self.stance.is_defensive() && self.surface_is_cheap(game, path, area) {
+            return None;
+        }
+        Some(dir)
+    }
+
+    // Silence is kept for when the opponent knows where we are. As the
+    // opponent candidates are the same wherever we go, the destination is
+    // the one looking like most of them, so that our next moves tell little.
+    // The silence is kept while we are well hidden, and at our last life for
+    // when we are stuck
+    pub fn keep_silence(&self, game: &Game) -> bool {
+        if Self::surface_is_lethal(game) {
+            return true;
+        }
+        let nb_seen = game.me.seen.candidates(game.map.h, game.map.w).nb_true();
+        nb_seen > self.config.silence_exposed_max && !self.surfaced && !self.stance.is_defensive()
+    }
+
+    pub fn plan_silence(&self, game: &Game) -> Option<(Direction, usize)> {
+        if game.me.silence > 0 {
+            return None;
+        }
+        let mut seen = game.me.seen.clone();
+        seen.apply(&Action::Silence(Direction::N, 0), &game.map);
+        let seen = seen.candidates(game.map.h, game.map.w);
+
+        let mut options = vec![(Direction::N, 0, game.me.pos, game.me.forbidden_map.clone())];
+        for dir in [Direction::E, Direction::N, Direction::W, Direction::S].iter() {
+            let mut pos = game.me.pos;
+            let mut map = game.me.forbidden_map.clone();
+            for dist in 1..=4 {
+                pos = match dir.apply(&pos) {
+                    Ok(pos) if pos.x < map.w && pos.y < map.h && !map.get(pos.y, pos.x) => pos,
+                    _ => break,
+                };
+                map.set(pos.y, pos.x, true);
+                options.push((*dir, dist, pos, map.clone()));
+            }
+        }
+
+        let (dir, dist, lookalikes, path) = options
+            .into_iter()
+            .map(|(dir, dist, pos, mut map)| {
+                let mut budget = self.config.silence_search_budget;
+                let path = map.longest_path(&pos, self.config.silence_min_path, &mut budget);
+                let signature = neighbourhood_signature(&game.map, &pos, 1);
+                let lookalikes = (0..game.map.h)
+                    .flat_map(|y| (0..game.map.w).map(move |x| Pos { y, x }))
+                    .filter(|cell| seen.get(cell.y, cell.x))
+                    .filter(|cell| neighbourhood_signature(&game.map, cell, 1) == signature)
+                    .count();
+                (dir, dist, lookalikes, path)
+            })
+            .max_by_key(|(_, dist, lookalikes, path)| (*path, *lookalikes, *dist))?;
+        log!(
+            Planner,
+            Trace,
+            "Silence {} {}, lookalikes {}, path {}",
+            dir,
+            dist,
+            lookalikes,
+            path
+        );
+        Some((dir, dist))
+    }
+
+    pub fn play_attacks(
+        &self,
+        turn: &mut Game,
+        weights: &[f32],
+        now: Timing,
+        torpedo: Timing,
+        trigger: Timing,
+        damage: &mut f32,
+    ) -> Option<()> {
+        if torpedo == now {
+            // Firing tells where we are
+            if self.stance == Stance::Hide {
+                return None;
+            }
+            let (pos, ev) = self.best_torpedo(turn, weights)?;
+            if ev < self.min_ev(self.config.torpedo_min_ev) {
+                return None;
+            }
+            // The damage we take is counted on our life instead
+            let self_damage = blast_damage(&pos, &turn.me.pos) as f32;
+            turn.torpedo(pos).ok()?;
+            *damage += ev + self.self_damage_weight() * self_damage;
+        }
+        if trigger == now {
+            let (pos, ev) = self.best_trigger(turn, weights)?;
+            if ev < self.min_ev(self.config.mine_trigger_min_ev) {
+                return None;
+            }
+            turn.trigger(pos).ok()?;
+            *damage += ev;
+        }
+        Some(())
+    }
+
+    // Plays the turn on a copy of the game, None when one of its steps can not
+    // be played. Weights are those of the opponent candidates.
+    pub fn try_turn(
+        &self,
+        game: &Game,
+        weights: &[f32],
+        turn_move: TurnMove,
+        torpedo: Timing,
+        trigger: Timing,
+    ) -> Option<(f32, Game)> {
+        let mut turn = game.clone();
+        let mut damage = 0.0;
+        self.play_attacks(
+            &mut turn,
+            weights,
+            Timing::Before,
+            torpedo,
+            trigger,
+            &mut damage,
+        )?;
+        match turn_move {
+            TurnMove::Move(dir) => {
+                let system = self.plan_charge(&turn);
+                turn.move_to(&dir, &system).ok()?;
+            }
+            TurnMove::Silence => {
+                let (dir, dist) = self.plan_silence(&turn)?;
+                turn.silence(&dir, dist).ok()?;
+            }
+            TurnMove::MoveSilence(dir) => {
+                let system = self.plan_charge(&turn);
+                turn.move_to(&dir, &system).ok()?;
+                let (dir, dist) = self.plan_silence(&turn)?;
+                turn.silence(&dir, dist).ok()?;
+            }
+            TurnMove::Surface => turn.surface(),
+        }
+        self.play_attacks(
+            &mut turn,
+            weights,
+            Timing::After,
+            torpedo,
+            trigger,
+            &mut damage,
+        )?;
+        let features = EvalFeatures::new(
+            &turn.map,
+            &SubState::from_me(&turn.me),
+            &turn.seen_after_actions().candidates(turn.map.h, turn.map.w),
+            &turn.opp.candidates(&turn.map),
+            turn.opp.life as f32 - damage,
+        );
+        Some((self.config.eval_weights.score(&features), turn))
+    }
+
+    // Every playable turn made of one of the moves and attacks before or
+    // after it, with its heuristic score. Once the deadline is passed, the
+    // moves left are dropped as soon as there is a turn to play.
+    pub fn candidate_turns(
+        &self,
+        game: &Game,
+        weights: &[f32],
+        moves: &[TurnMove],
+        deadline: Instant,
+    ) -> Vec<(f32, Game)> {
+        let timings = [Timing::Never, Timing::Before, Timing::After];
+        let mut ret = vec![];
+        for turn_move in moves.iter() {
+            if !ret.is_empty() && Instant::now() >= deadline {
+                break;
+            }
+            for torpedo in timings.iter() {
+                for trigger in timings.iter() {
+                    let turn = self.try_turn(game, weights, *turn_move, *torpedo, *trigger);
+                    if let Some(turn) = turn {
+                        ret.push(turn);
+                    }
+                }
+            }
+        }
+        ret
+    }
+
+    pub fn surface_is_lethal(game: &Game) -> bool {
+        game.me.life <= 1
+    }
+
+    // Stuck with a single life left, a silence of 0 keeps us alive
+    pub fn turn_moves(&self, game: &Game, dir: Option<Direction>) -> Vec<TurnMove> {
+        match dir {
+            Some(dir) if self.keep_silence(game) => vec![TurnMove::Move(dir)],
+            Some(dir) => vec![
+                TurnMove::Move(dir),
+                TurnMove::Silence,
+                TurnMove::MoveSilence(dir),
+            ],
+            None if Self::surface_is_lethal(game) && game.me.silence == 0 => {
+                vec![TurnMove::Silence]
+            }
+            None => vec![TurnMove::Surface],
+        }
+    }
+
+    // How lost the opponent is once our orders are sent: bits of uncertainty
+    // and damage its best guess shot would deal us
+    pub fn deception_value(&self, game: &Game) -> f32 {
+        let seen = game.seen_after_actions().candidates(game.map.h, game.map.w);
+        let bits = (std::cmp::max(1, seen.nb_true()) as f32).log2();
+        let exposure = match best_guess_shot(&game.map, &seen) {
+            Some(shot) => blast_damage(&shot, &game.me.pos) as f32,
+            None => 0.0,
+        };
+        self.config.deception_bit_value * bits - self.config.deception_exposure_value * exposure
+    }
+
+    // Orders added to the turn only to mislead the opponent: a torpedo that
+    // moves its guess away from us, or a silence, even of 0, that spreads it.
+    // Silences come first, torpedo targets are only scored until the deadline.
+    pub fn decoys(&self, game: &Game, deadline: Instant) -> Vec<(Game, f32)> {
+        let mut ret = vec![];
+        let silenced = game
+            .actions
+            .iter()
+            .any(|action| matches!(action, Action::Silence(_, _)));
+        if !silenced && game.me.silence == 0 && !Self::surface_is_lethal(game) {
+            for dir in [Direction::E, Direction::N, Direction::W, Direction::S].iter() {
+                for dist in 0..=4 {
+                    let mut turn = game.clone();
+                    if turn.silence(dir, dist).is_err() {
+                        break;
+                    }
+                    let mut budget = self.config.silence_search_budget;
+                    let pos = turn.me.pos;
+                    let path = turn.me.forbidden_map.clone().longest_path(
+                        &pos,
+                        self.config.silence_min_path,
+                        &mut budget,
+                    );
+                    if path >= self.config.silence_min_path {
+                        ret.push((turn, self.config.decoy_silence_cost));
+                    }
+                }
+            }
+        }
+        if self.stance != Stance::Hide && game.me.torpedo == 0 && Instant::now() < deadline {
+            let weights = game.opp.weights(&game.map);
+            let dists = game.map.distances_from(&game.me.pos, 4);
+            let targets = (0..game.map.h)
+                .flat_map(|y| (0..game.map.w).map(move |x| Pos { y, x }))
+                .filter(|pos| dists[pos.y * game.map.w + pos.x].is_some())
+                .filter(|pos| blast_damage(pos, &game.me.pos) == 0);
+            for pos in targets {
+                if Instant::now() >= deadline {
+                    break;
+                }
+                let mut turn = game.clone();
+                if turn.torpedo(pos).is_ok() {
+                    let cost =
+                        self.config.decoy_torpedo_cost - Self::blast_ev(game, &weights, &pos);
+                    ret.push((turn, cost));
+                }
+            }
+        }
+        ret
+    }
+
+    pub fn plan_deception(&self, game: &mut Game) {
+        let deadline = game.clock.deadline();
+        let base = self.deception_value(game);
+        let best = self
+            .decoys(game, deadline)
+            .into_iter()
+            .take_while(|_| Instant::now() < deadline)
+            .map(|(turn, cost)| {
+                let gain = self.deception_value(&turn) - base - cost;
+                (turn, gain)
+            })
+            .max_by(|(_, max), (_, v)| max.partial_cmp(v).unwrap());
+        if let Some((turn, gain)) = best {
+            if gain > 0.0 {
+                log!(
+                    Planner,
+                    Info,
+                    "Decoy {:?}, gain {}",
+                    turn.actions.last(),
+                    gain
+                );
+                *game = turn;
+            }
+        }
+    }
+
+    pub fn plan_actions(&mut self, game: &mut Game) {
+        self.stance = self.stance(game);
+        log!(Planner, Info, "Stance: {:?}", self.stance);
+        let dir = self.plan_move(game);
+        let deadline = game.clock.deadline_at(self.config.search_time_share);
+        *game = self.search_turn(game, dir, deadline);
+        // The search may play another move than the planner's
+        self.surfaced = game
+            .actions
+            .iter()
+            .any(|action| matches!(action, Action::Surface(_)));
+        self.dir = game.actions.iter().rev().find_map(|action| match action {
+            Action::Move(dir, _) => Some(*dir),
+            Action::Silence(dir, dist) if *dist > 0 => Some(*dir),
+            _ => None,
+        });
+
+        self.plan_deception(game);
+        self.plan_sonar(game);
+        self.plan_mine(game);
+    }
+}
+
+// -----------------------------------------------------------------------
+// Search
+// -----------------------------------------------------------------------
+// Rollout policy: fire at the best blast target from the belief about the
+// enemy when the expected damage reaches min_ev, then move to the free
+// neighbour with the most exits, charging the torpedo. The direction bias,
+// indexed N, E, S, W, is added to the exits.
+pub fn rollout_turn(
+    rules: &Rules,
+    player: usize,
+    belief: &Map,
+    min_ev: f32,
+    dir_bias: &[f32; 4],
+) -> Vec<Action> {
+    let sub = &rules.subs[player];
+    let mut actions = vec![];
+    let total = belief.nb_true();
+    if sub.torpedo == 0 && total > 0 {
+        let dists = rules.map.distances_from(&sub.pos, 4);
+        let best = (0..rules.map.h)
+            .flat_map(|y| (0..rules.map.w).map(move |x| Pos { y, x }))
+            .filter(|pos| dists[pos.y * rules.map.w + pos.x].is_some())
+            .filter(|pos| blast_damage(pos, &sub.pos) == 0)
+            .map(|pos| {
+                let mut ring_map = rules.map.square(&pos);
+                ring_map.set(pos.y, pos.x, false);
+                let hits = 2 * belief.get(pos.y, pos.x) as usize + belief.nb_true_and(&ring_map);
+                (pos, hits as f32 / total as f32)
+            })
+            .max_by(|(_, max), (_, v)| max.partial_cmp(v).unwrap());
+        if let Some((pos, ev)) = best {
+            if ev >= min_ev {
+                actions.push(Action::Torpedo(pos));
+            }
+        }
+    }
+    let next = [Direction::N, Direction::E, Direction::S, Direction::W]
+        .iter()
+        .zip(dir_bias.iter())
+        .filter_map(|(dir, bias)| {
+            let next = dir.apply(&sub.pos).ok()?;
+            if next.x >= rules.map.w || next.y >= rules.map.h {
+                return None;
+            }
+            if sub.forbidden_map.get(next.y, next.x) {
+                return None;
+            }
+            let exits = sub.forbidden_map.free_neighbours(&next).len() as f32;
+            Some((*dir, exits + bias))
+        })
+        .max_by(|(_, max), (_, v)| max.partial_cmp(v).unwrap());
+    match next {
+        Some((dir, _)) => actions.push(Action::Move(dir, System::Torpedo)),
+        None => actions.push(Action::Surface(rules.map.sector(&sub.pos))),
+    }
+    actions
+}
+
+impl Ai {
+    pub fn sample_opp_pos(&mut self, weights: &[f32], w: usize) -> Option<Pos> {
+        let total: f32 = weights.iter().sum();
+        if total <= 0.0 {
+            return None;
+        }
+        let mut r = self.rng.gen_range(0.0, total);
+        let i = weights
+            .iter()
+            .position(|weight| {
+                r -= weight;
+                r < 0.0
+            })
+            .unwrap_or_else(|| weights.iter().rposition(|weight| *weight > 0.0).unwrap());
+        Some(Pos { y: i / w, x: i % w })
+    }
+
+    // Plays our turn then a few plies of both sides with the opponent at pos
+    pub fn simulate(&self, game: &Game, turn: &Game, opp_pos: Pos, nb_plies: usize) -> f32 {
+        let mut opp = SubState::new(&game.map, opp_pos);
+        opp.life = game.opp.life;
+        opp.torpedo = game.opp.torpedo;
+        let mut rules = Rules::new(game.map.clone(), [SubState::from_me(&game.me), opp]);
+        if rules.play(0, &turn.actions).is_err() {
+            return f32::MIN;
+        }
+        let opp_belief = turn.seen_after_actions().candidates(game.map.h, game.map.w);
+        let me_belief = game.opp.candidates(&game.map);
+        // A neutral opponent fires half the time it can
+        let opp_min_ev = self.config.torpedo_min_ev / (2.0 * game.opp.profile.fire_rate());
+        let opp_bias = game
+            .opp
+            .profile
+            .dir_bias()
+            .map(|bias| self.config.rollout_dir_bias_weight * bias);
+        for ply in 0..nb_plies {
+            if rules.is_over() {
+                break;
+            }
+            let actions = rollout_turn(&rules, 1, &opp_belief, opp_min_ev, &opp_bias);
+            let _ = rules.play(1, &actions);
+            if rules.is_over() || ply + 1 == nb_plies {
+                break;
+            }
+            let actions =
+                rollout_turn(&rules, 0, &me_belief, self.config.torpedo_min_ev, &[0.0; 4]);
+            let _ = rules.play(0, &actions);
+        }
+        let features = EvalFeatures::new(
+            &rules.map,
+            &rules.subs[0],
+            &opp_belief,
+            &me_belief,
+            rules.subs[1].life as f32,
+        );
+        self.config.eval_weights.score(&features)
+    }
+
+    // Every candidate turn is played against opponent positions sampled from
+    // its candidates, one more ply deep at each iteration. The best mean
+    // outcome of the last complete depth is kept when the deadline comes.
+    pub fn search_turn(&mut self, game: &Game, dir: Option<Direction>, deadline: Instant) -> Game {
+        let start = Instant::now();
+        // The planner moves come first, so that they are scored even out of
+        // time. The other safe moves are tried too, unless the planner
+        // found it better to surface.
+        let safe_dirs = match dir {
+            Some(_) => self.safe_dirs(game),
+            None => vec![],
+        };
+        let mut moves = self.turn_moves(game, dir);
+        for d in [Direction::E, Direction::N, Direction::W, Direction::S].iter() {
+            if safe_dirs.contains(d) && !moves.contains(&TurnMove::Move(*d)) {
+                moves.push(TurnMove::Move(*d));
+            }
+        }
+        let weights = game.opp.weights(&game.map);
+        let turns = self.candidate_turns(game, &weights, &moves, deadline);
+        if turns.is_empty() {
+            return game.clone();
+        }
+
+        // Until a depth is complete, the heuristic score decides
+        let mut values: Vec<_> = turns.iter().map(|(prior, _)| *prior).collect();
+        let mut depth = 0;
+        for nb_plies in 1..=self.config.search_max_plies {
+            let mut totals = vec![0.0; turns.len()];
+            let mut nb_samples = 0;
+            while nb_samples < self.config.search_samples_per_depth && Instant::now() < deadline {
+                let opp_pos = match self.sample_opp_pos(&weights, game.map.w) {
+                    Some(pos) => pos,
+                    None => break,
+                };
+                for (total, (_, turn)) in totals.iter_mut().zip(turns.iter()) {
+                    *total += self.simulate(game, turn, opp_pos, nb_plies);
+                }
+                nb_samples += 1;
+            }
+            if nb_samples < self.config.search_samples_per_depth {
+                break;
+            }
+            values = totals
+                .iter()
+                .zip(turns.iter())
+                .map(|(total, (prior, _))| {
+                    total / nb_samples as f32 + self.config.search_prior_weight * prior
+                })
+                .collect();
+            depth = nb_plies;
+        }
+        let (index, value) = values
+            .iter()
+            .enumerate()
+            .max_by(|(_, max), (_, v)| max.partial_cmp(v).unwrap())
+            .unwrap();
+        log!(
+            Planner,
+            Info,
+            "Search: {} turns, depth {}, best {:?} value {} in {:?}",
+            turns.len(),
+            depth,
+            turns[index].1.actions,
+            value,
+            start.elapsed()
+        );
+        turns[index].1.clone()
+    }
+}
+
+// Tests -----------------------------------------------------------------
+#[test]
+fn test_best_sonar_sector() {
+    let mut game = Game::new(Map::new(15, 15), 0);
+    let mut candidates = Map::new(15, 15);
+    candidates.set(0, 0, true);
+    candidates.set(0, 14, true);
+    game.opp.pos = OppPos::Area(candidates.clone());
+    let ai = Ai::new();
+    let (sector, gain) = ai.best_sonar_sector(&game).unwrap();
+    assert!(sector == 1 || sector == 3);
+    assert!((gain - 1.0).abs() < 1e-6);
+
+    // No sonar was sent, the answer must be ignored
+    game.apply_sonar_result("N");
+    assert_eq!(game.opp.pos, OppPos::Area(candidates));
+
+    game.last_turn.sonar = Some(3);
+    game.apply_sonar_result("N");
+    assert_eq!(game.opp.pos, OppPos::Exact(Pos { y: 0, x: 0 }));
+}
+
+#[test]
+fn test_update_opponent_single_candidate() {
+    let mut game = Game::new(Map::new(15, 15), 0);
+    let mut candidates = Map::new(15, 15);
+    candidates.set(0, 0, true);
+    candidates.set(0, 14, true);
+    game.opp.pos = OppPos::Area(candidates);
+    // Only one candidate is left in sector 1
+    game.update_opponent("SURFACE 1");
+    assert_eq!(game.opp.pos, OppPos::Exact(Pos { y: 0, x: 0 }));
+}
+
+#[test]
+fn test_plan_trigger() {
+    let mut game = Game::new(Map::new(15, 15), 0);
+    game.me.pos = Pos { y: 0, x: 0 };
+    game.me.mines = vec![Pos { y: 1, x: 1 }, Pos { y: 10, x: 10 }];
+    game.opp.pos = OppPos::Exact(Pos { y: 1, x: 2 });
+    let ai = Ai::new();
+    let min_ev = ai.min_ev(ai.config.mine_trigger_min_ev);
+
+    // The closest mine would hit us too
+    let weights = game.opp.weights(&game.map);
+    let (mine, ev) = ai.best_trigger(&game, &weights).unwrap();
+    assert_eq!(mine, Pos { y: 10, x: 10 });
+    assert!(ev < min_ev);
+
+    game.opp.pos = OppPos::Exact(Pos { y: 10, x: 11 });
+    let weights = game.opp.weights(&game.map);
+    let (mine, ev) = ai.best_trigger(&game, &weights).unwrap();
+    assert_eq!(mine, Pos { y: 10, x: 10 });
+    assert!(ev >= min_ev);
+}
+
+#[test]
+fn test_opp_mines() {
+    let map = Map::new(15, 15);
+    let mut game = Game::new(map.clone(), 0);
+    game.opp.pos = OppPos::Exact(Pos { y: 0, x: 5 });
+    game.update_opponent("MINE");
+    let danger = game.opp.mines.danger();
+    assert!((danger[6] - 1.0 / 3.0).abs() < 1e-6);
+    assert!((danger[15 + 5] - 1.0 / 3.0).abs() < 1e-6);
+    assert_eq!(danger[5], 0.0);
+
+    game.me.pos = Pos { y: 0, x: 3 };
+    let ai = Ai::new();
+    let dirs = ai.avoid_mines(&game, vec![Direction::E, Direction::S]);
+    assert_eq!(dirs, vec![Direction::S]);
+
+    game.update_opponent("TRIGGER 6 0");
+    assert!(game.opp.mines.mines.is_empty());
+}
+
+#[test]
+fn test_avoid_mines_dead_end() {
+    // West is a one cell pocket, north the open water a mine waits next to
+    let mut map = Map::new(15, 15);
+    map.set(6, 6, true);
+    map.set(8, 6, true);
+    map.set(7, 5, true);
+    let mut game = Game::new(map, 0);
+    game.me.pos = Pos { y: 7, x: 7 };
+    for (y, x) in [(7, 7), (7, 8), (8, 7)].iter() {
+        game.me.forbidden_map.set(*y, *x, true);
+    }
+    game.opp.pos = OppPos::Exact(Pos { y: 0, x: 0 });
+    let mut mine = vec![0.0; 15 * 15].into_boxed_slice();
+    mine[5 * 15 + 7] = 1.0;
+    game.opp.mines.mines.push(mine);
+    let mut ai = Ai::new();
+    assert!(!ai.has_route(&game, &Direction::W, 2));
+    assert_eq!(ai.safe_dirs(&game), vec![Direction::N]);
+    assert_eq!(ai.plan_move(&mut game), Some(Direction::N));
+}
+
+#[test]
+fn test_longest_path() {
+    let mut map = Map::new(3, 3);
+    map.set(0, 0, true);
+    let mut budget = AiConfig::new().path_search_budget;
+    assert_eq!(map.longest_path(&Pos { y: 0, x: 0 }, 20, &mut budget), 8);
+    assert_eq!(map, {
+        let mut map = Map::new(3, 3);
+        map.set(0, 0, true);
+        map
+    });
+
+    // The middle of a corridor splits it in two
+    let corridor = Map::new(1, 5);
+    assert_eq!(corridor.usable_area(&Pos { y: 0, x: 2 }), 2);
+}
+
+#[test]
+fn test_plan_move_longest_route() {
+    let map = Map::new(3, 6);
+    let mut game = Game::new(map, 0);
+    // Our trail cuts the map, leaving a 4 cells pocket to the west against 9
+    // to the east
+    for y in 0..3 {
+        game.me.forbidden_map.set(y, 2, true);
+    }
+    game.me.forbidden_map.set(0, 0, true);
+    game.me.forbidden_map.set(1, 0, true);
+    game.me.pos = Pos { y: 1, x: 2 };
+    let mut ai = Ai::new();
+    ai.dir = Some(Direction::W);
+    assert_eq!(ai.plan_move(&mut game), Some(Direction::E));
+}
+
+#[test]
+fn test_rand_false_pos() {
+    let mut map = Map::new(2, 5);
+    map.invert();
+    map.set(1, 4, false);
+    let mut rng = rand::rngs::StdRng::seed_from_u64(0);
+    assert_eq!(map.rand_false_pos(&mut rng), Ok(Pos { y: 1, x: 4 }));
+}
+
+#[test]
+fn test_score_start_points() {
+    // A single cell pocket in the corner, cut from the open sea
+    let mut map = Map::new(15, 15);
+    map.set(0, 1, true);
+    map.set(1, 0, true);
+    map.set(1, 1, true);
+    let ai = Ai::new();
+    let scores = ai.score_start_points(&map);
+    let (best, _) = scores
+        .iter()
+        .max_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap())
+        .unwrap();
+    assert_ne!(*best, Pos { y: 0, x: 0 });
+    let (worst, _) = scores
+        .iter()
+        .min_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap())
+        .unwrap();
+    assert_eq!(*worst, Pos { y: 0, x: 0 });
+}
+
+#[test]
+fn test_plan_silence() {
+    let mut game = Game::new(Map::new(15, 15), 0);
+    game.me.pos = Pos { y: 7, x: 7 };
+    game.me.silence = 0;
+    let ai = Ai::new();
+    // Hidden enough to keep the silence
+    assert!(ai.keep_silence(&game));
+
+    game.me.seen = OppPos::Exact(game.me.pos);
+    assert!(!ai.keep_silence(&game));
+    let (dir, dist) = ai.plan_silence(&game).unwrap();
+    assert_eq!(dist, 4);
+    game.silence(&dir, dist).unwrap();
+    game.commit();
+    assert_eq!(game.me.seen.candidates(15, 15).nb_true(), 17);
+}
+
+#[test]
+fn test_plan_charge() {
+    let mut game = Game::new(Map::new(15, 15), 0);
+    game.me.pos = Pos { y: 7, x: 7 };
+    let mut ai = Ai::new();
+    ai.config = AiConfig::parse("charge.torpedo=1\ncharge.sonar=0.6\ncharge.silence=0.8").unwrap();
+
+    game.opp.pos = OppPos::Exact(Pos { y: 2, x: 2 });
+    assert_eq!(ai.plan_charge(&game), System::Torpedo);
+    game.me.torpedo = 0;
+    game.me.seen = OppPos::Exact(game.me.pos);
+    assert_eq!(ai.plan_charge(&game), System::Silence);
+
+    assert!(AiConfig::parse("charge.laser=1").is_err());
+}
+
+#[test]
+fn test_torpedo_range() {
+    let mut map = Map::new(15, 15);
+    map.set(7, 8, true);
+    let mut game = Game::new(map, 0);
+    game.me.pos = Pos { y: 7, x: 7 };
+    game.me.torpedo = 0;
+    // The island makes the path one step longer
+    assert_eq!(game.torpedo(Pos { y: 7, x: 10 }), Err(()));
+    assert_eq!(game.torpedo(Pos { y: 7, x: 9 }), Ok(()));
+    assert_eq!(game.me.torpedo, cooldown::TORPEDO);
+}
+
+#[test]
+fn test_turn_heuristic() {
+    let mut game = Game::new(Map::new(15, 15), 0);
+    game.me.pos = Pos { y: 7, x: 7 };
+    game.me.torpedo = 1;
+    game.opp.pos = OppPos::Exact(Pos { y: 7, x: 12 });
+    let mut ai = Ai::new();
+    // Without rollouts, the heuristic score alone picks the turn
+    ai.config.search_max_plies = 0;
+    let deadline = Instant::now() + Duration::from_secs(1);
+
+    // Out of range and not charged, unless we move towards it charging
+    let turn = ai.search_turn(&game, Some(Direction::E), deadline);
+    assert_eq!(
+        turn.actions,
+        vec![
+            Action::Move(Direction::E, System::Torpedo),
+            Action::Torpedo(Pos { y: 7, x: 12 })
+        ]
+    );
+
+    // Within range, the torpedo goes first so that the silence hides us
+    game.me.torpedo = 0;
+    game.me.silence = 0;
+    game.me.pos = Pos { y: 7, x: 9 };
+    game.me.seen = OppPos::Exact(game.me.pos);
+    let turn = ai.search_turn(&game, Some(Direction::W), deadline);
+    assert_eq!(turn.actions[0], Action::Torpedo(Pos { y: 7, x: 12 }));
+    assert!(matches!(turn.actions[1], Action::Silence(_, _)));
+}
+
+#[test]
+fn test_clock() {
+    let clock = Clock {
+        start: Instant::now(),
+        budget: TURN_TIME,
+        margin: Duration::from_millis(10),
+    };
+    assert_eq!(clock.deadline(), clock.start + Duration::from_millis(40));
+    let search = clock.deadline_at(0.5) - clock.start;
+    assert!(search > Duration::from_millis(19) && search < Duration::from_millis(21));
+    let clock = Clock {
+        margin: Duration::from_millis(60),
+        ..clock
+    };
+    assert_eq!(clock.deadline(), clock.start);
+}
+
+#[test]
+fn test_search_turn() {
+    let mut game = Game::new(Map::new(15, 15), 0);
+    game.me.pos = Pos { y: 7, x: 7 };
+    game.me.torpedo = 0;
+    let mut candidates = Map::new(15, 15);
+    candidates.set(7, 10, true);
+    candidates.set(7, 11, true);
+    game.opp.pos = OppPos::Area(candidates);
+    let mut ai = Ai::new();
+    // Deeper, our own rollouts fire the next ply anyway
+    ai.config.search_max_plies = 1;
+    let deadline = Instant::now() + Duration::from_secs(1);
+    let turn = ai.search_turn(&game, Some(Direction::N), deadline);
+    assert!(turn
+        .actions
+        .iter()
+        .any(|action| matches!(action, Action::Torpedo(_))));
+
+    // Out of time, the heuristic score still gives a turn
+    let turn = ai.search_turn(&game, Some(Direction::N), Instant::now());
+    assert!(turn
+        .actions
+        .iter()
+        .any(|action| matches!(action, Action::Torpedo(_))));
+}
+
+#[test]
+fn test_turn_evaluation() {
+    let mut game = Game::new(Map::new(15, 15), 0);
+    game.me.pos = Pos { y: 7, x: 7 };
+    game.me.torpedo = 0;
+    game.opp.pos = OppPos::Exact(Pos { y: 7, x: 10 });
+    let mut ai = Ai::new();
+    let weights = game.opp.weights(&game.map);
+    let turn_move = TurnMove::Move(Direction::N);
+    let (fire, _) = ai
+        .try_turn(&game, &weights, turn_move, Timing::Before, Timing::Never)
+        .unwrap();
+    let (hold, _) = ai
+        .try_turn(&game, &weights, turn_move, Timing::Never, Timing::Never)
+        .unwrap();
+    assert!(fire > hold);
+    let deadline = Instant::now() + Duration::from_secs(1);
+    let turn = ai.search_turn(&game, Some(Direction::N), deadline);
+    assert!(turn
+        .actions
+        .iter()
+        .any(|action| matches!(action, Action::Torpedo(_))));
+
+    // Islands behind the opponent leave only shots that hit us too
+    let mut map = Map::new(15, 15);
+    for y in 6..=8 {
+        map.set(y, 9, true);
+    }
+    let mut game = Game::new(map, 0);
+    game.me.pos = Pos { y: 7, x: 7 };
+    game.me.torpedo = 0;
+    game.opp.pos = OppPos::Exact(Pos { y: 7, x: 8 });
+    let weights = game.opp.weights(&game.map);
+    let (_, turn) = ai
+        .try_turn(
+            &game,
+            &weights,
+            TurnMove::Move(Direction::W),
+            Timing::Before,
+            Timing::Never,
+        )
+        .unwrap();
+    assert_eq!(turn.actions[0], Action::Torpedo(Pos { y: 7, x: 8 }));
+    assert_eq!(turn.me.life, MAX_LIFE - 1);
+}
+
+#[test]
+fn test_search_turn_safe_moves() {
+    let mut game = Game::new(Map::new(15, 15), 0);
+    game.me.pos = Pos { y: 7, x: 7 };
+    game.me.torpedo = 1;
+    game.opp.pos = OppPos::Exact(Pos { y: 7, x: 12 });
+    let mut ai = Ai::new();
+    let deadline = Instant::now() + Duration::from_secs(1);
+    // Only going east charges the torpedo within range
+    let turn = ai.search_turn(&game, Some(Direction::N), deadline);
+    assert_eq!(turn.actions[0], Action::Move(Direction::E, System::Torpedo));
+
+    // A mine surely waits there
+    let mut mine = vec![0.0; 15 * 15].into_boxed_slice();
+    mine[6 * 15 + 9] = 1.0;
+    game.opp.mines.mines.push(mine);
+    let dir = ai.plan_move(&mut game);
+    assert!(dir.is_some() && dir != Some(Direction::E));
+    let turn = ai.search_turn(&game, dir, deadline);
+    assert!(!turn
+        .actions
+        .iter()
+        .any(|action| matches!(action, Action::Move(Direction::E, _))));
+}
+
+#[test]
+fn test_plan_actions_surfaced() {
+    // Stuck in a corner with a life left, we silence in place instead
+    let mut game = Game::new(Map::new(15, 15), 0);
+    game.me.pos = Pos { y: 0, x: 0 };
+    game.me.forbidden_map.set(0, 1, true);
+    game.me.forbidden_map.set(1, 0, true);
+    game.me.life = 1;
+    game.me.silence = 0;
+    game.opp.pos = OppPos::Exact(Pos { y: 14, x: 14 });
+    let mut ai = Ai::new();
+    assert_eq!(ai.plan_move(&mut game), None);
+    ai.plan_actions(&mut game);
+    assert!(matches!(game.actions[0], Action::Silence(_, 0)));
+    assert!(!ai.surfaced);
+}
+
+#[test]
+fn test_defensive_stance() {
+    let mut game = Game::new(Map::new(15, 15), 0);
+    game.me.life = 1;
+    game.me.pos = Pos { y: 7, x: 7 };
+    game.opp.pos = OppPos::Exact(Pos { y: 7, x: 14 });
+    game.opp.torpedo = 0;
+    let mut ai = Ai::new();
+    ai.stance = ai.stance(&game);
+    assert_eq!(ai.stance, Stance::Defensive);
+
+    // Going east brings us in reach
+    let threat = game.torpedo_threat();
+    assert!(threat.get(7, 8));
+    assert!(!threat.get(7, 6));
+    let dirs = vec![Direction::E, Direction::N, Direction::W, Direction::S];
+    assert_eq!(
+        ai.avoid_torpedoes(&game, dirs),
+        vec![Direction::N, Direction::W, Direction::S]
+    );
+
+    // Stuck, surfacing would kill us
+    game.me.silence = 0;
+    assert_eq!(ai.turn_moves(&game, None), vec![TurnMove::Silence]);
+    game.me.forbidden_map.invert();
+    let turn = ai.search_turn(&game, None, Instant::now());
+    assert_eq!(turn.actions, vec![Action::Silence(Direction::N, 0)]);
+}
+
+#[test]
+fn test_defensive_dead_end() {
+    // West is a one cell pocket out of reach, north the open water in reach
+    let mut map = Map::new(15, 15);
+    map.set(6, 6, true);
+    map.set(8, 6, true);
+    map.set(7, 5, true);
+    let mut game = Game::new(map, 0);
+    game.me.life = 1;
+    game.me.silence = 0;
+    game.me.pos = Pos { y: 7, x: 7 };
+    for (y, x) in [(7, 7), (7, 8), (8, 7)].iter() {
+        game.me.forbidden_map.set(*y, *x, true);
+    }
+    game.opp.pos = OppPos::Exact(Pos { y: 0, x: 7 });
+    game.opp.torpedo = 0;
+    let mut ai = Ai::new();
+    ai.stance = ai.stance(&game);
+    assert_eq!(ai.stance, Stance::Defensive);
+    let threat = game.torpedo_threat();
+    assert!(threat.get(6, 7) && !threat.get(7, 6));
+    assert_eq!(ai.plan_move(&mut game), Some(Direction::N));
+
+    // The silence is kept for when we are stuck
+    assert_eq!(
+        ai.turn_moves(&game, Some(Direction::N)),
+        vec![TurnMove::Move(Direction::N)]
+    );
+    game.me.seen = OppPos::Exact(game.me.pos);
+    let mut turn = game.clone();
+    ai.plan_deception(&mut turn);
+    assert!(turn.actions.is_empty());
+}
+
+#[test]
+fn test_endgame_stance() {
+    let mut game = Game::new(Map::new(15, 15), 0);
+    game.me.pos = Pos { y: 7, x: 7 };
+    game.opp.pos = OppPos::Exact(Pos { y: 7, x: 2 });
+    game.me.life = 3;
+    game.opp.life = 4;
+    let mut ai = Ai::new();
+    assert_eq!(ai.stance(&game), Stance::Normal);
+
+    game.turn = MAX_TURNS - ai.config.endgame_turns;
+    assert_eq!(ai.stance(&game), Stance::Engage);
+    ai.stance = Stance::Engage;
+    assert_eq!(ai.plan_move(&mut game), Some(Direction::W));
+
+    game.opp.life = 2;
+    assert_eq!(ai.stance(&game), Stance::Hide);
+    game.opp.life = 3;
+    assert_eq!(ai.stance(&game), Stance::Normal);
+}
+
+#[test]
+fn test_opp_profile() {
+    let mut game = Game::new(Map::new(15, 15), 0);
+    game.opp.pos = OppPos::Exact(Pos { y: 7, x: 7 });
+    game.update_opponent("MOVE N");
+    game.update_opponent("MOVE N|SILENCE");
+    game.update_opponent("MOVE E");
+    assert_eq!(game.opp.profile.silence_dists, [0; 5]);
+
+    // Located: the silence went 2 cells north
+    game.opp.pos = OppPos::Exact(Pos { y: 3, x: 8 });
+    game.update_opponent("MOVE E");
+    assert_eq!(game.opp.profile.silence_dists, [0, 0, 1, 0, 0]);
+    assert_eq!(game.opp.profile.moves, [2, 2, 0, 0]);
+    let bias = game.opp.profile.dir_bias();
+    assert!(bias[0] > 1.0 && bias[2] < 1.0);
+
+    // The next silence is more likely to go that far
+    game.update_opponent("SILENCE");
+    let weights = game.opp.weights(&game.map);
+    assert!(weights[3 * 15 + 11] > weights[3 * 15 + 10]);
+    assert!(weights[3 * 15 + 11] > weights[3 * 15 + 12]);
+    assert_eq!(weights[0], 0.0);
+}
+
+#[test]
+fn test_plan_deception() {
+    let mut game = Game::new(Map::new(15, 15), 0);
+    game.me.pos = Pos { y: 7, x: 7 };
+    game.me.forbidden_map.set(7, 7, true);
+    game.me.torpedo = 0;
+    game.me.silence = 0;
+    let ai = Ai::new();
+
+    // Lost opponent: nothing is worth spending a system on
+    let mut turn = game.clone();
+    ai.plan_deception(&mut turn);
+    assert!(turn.actions.is_empty());
+
+    // Located: a silence spreads the opponent guess
+    game.me.seen = OppPos::Exact(game.me.pos);
+    let mut turn = game.clone();
+    ai.plan_deception(&mut turn);
+    match turn.actions.as_slice() {
+        [Action::Silence(_, _)] => (),
+        actions => panic!("{:?}", actions),
+    }
+
+    // Out of time, no decoy is scored
+    game.clock.budget = Duration::from_millis(0);
+    let mut turn = game.clone();
+    ai.plan_deception(&mut turn);
+    assert!(turn.actions.is_empty());
+}
+
+#[test]
+fn test_ai_config() {
+    let config = AiConfig::parse(
+        "torpedo_min_ev = 0.25\nsearch_max_plies=2\ncharge.mine=1 # More mines\n\neval.life_diff=2",
+    )
+    .unwrap();
+    assert_eq!(config.torpedo_min_ev, 0.25);
+    assert_eq!(config.search_max_plies, 2);
+    assert_eq!(config.charge_priorities.mine, 1.0);
+    assert_eq!(config.eval_weights.life_diff, 2.0);
+    assert_eq!(config.eval_weights.charged, EvalWeights::new().charged);
+    assert_eq!(config.sonar_min_gain, AiConfig::new().sonar_min_gain);
+    assert_eq!(AiConfig::parse(&config.to_string()), Ok(config));
+
+    assert!(AiConfig::parse("search_max_plies = 2.5").is_err());
+    assert!(AiConfig::parse("defensive_life = -1").is_ok());
+    assert!(AiConfig::parse("endgame_turns = -1").is_err());
+    assert!(AiConfig::parse("path_search_depth = 0").is_err());
+    assert!(AiConfig::parse("search_samples_per_depth = 0").is_err());
+    assert!(AiConfig::parse("search_time_share = 1.5").is_err());
+    assert!(AiConfig::parse("eval.hidden = 100").is_err());
+    assert_eq!(AiConfig::range("charge.mine"), Ok(CHARGE_PRIORITY_RANGE));
+    assert!(AiConfig::parse("unknown = 1").is_err());
+    assert!(AiConfig::parse("eval.unknown = 1").is_err());
+}
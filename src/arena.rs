@@ -0,0 +1,339 @@
+use crate::bot::*;
+use crate::geometry::*;
+use crate::replay::*;
+use rand::SeedableRng;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+// =======================================================================
+// Arena
+// =======================================================================
+// Tournaments between bots on a pool of generated maps. Each pairing plays
+// every map twice, once with each player id, as player 0 moves first.
+
+// Runs f(0..n) on threads, giving the results in order
+pub fn run_parallel<T, F>(n: usize, threads: usize, f: F) -> Vec<T>
+where
+    T: Send,
+    F: Fn(usize) -> T + Sync,
+{
+    let next = AtomicUsize::new(0);
+    let results = Mutex::new((0..n).map(|_| None).collect::<Vec<_>>());
+    std::thread::scope(|scope| {
+        for _ in 0..std::cmp::max(threads, 1) {
+            scope.spawn(|| loop {
+                let i = next.fetch_add(1, Ordering::SeqCst);
+                if i >= n {
+                    break;
+                }
+                let result = f(i);
+                results.lock().unwrap()[i] = Some(result);
+            });
+        }
+    });
+    results
+        .into_inner()
+        .unwrap()
+        .into_iter()
+        .map(|result| result.unwrap())
+        .collect()
+}
+
+pub fn map_pool(seed: u64, nb_maps: usize) -> Vec<Map> {
+    let mut rng = rand::rngs::StdRng::seed_from_u64(seed);
+    (0..nb_maps)
+        .map(|_| Map::random(MATCH_MAP_SIZE, MATCH_MAP_SIZE, MATCH_ISLAND_RATIO, &mut rng))
+        .collect()
+}
+
+// -----------------------------------------------------------------------
+// Tournament
+// -----------------------------------------------------------------------
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GameResult {
+    // Bots playing as player 0 and 1
+    pub bots: [usize; 2],
+    pub map: usize,
+    // Player who won, None for a draw
+    pub winner: Option<usize>,
+}
+
+impl GameResult {
+    // Points of bot: 1 for a win, 0.5 for a draw
+    pub fn score(&self, bot: usize) -> f32 {
+        match self.winner {
+            Some(player) if self.bots[player] == bot => 1.0,
+            Some(_) => 0.0,
+            None => 0.5,
+        }
+    }
+}
+
+pub struct Tournament {
+    // Bot names, as new_bot knows them
+    pub bots: Vec<String>,
+    pub maps: Vec<Map>,
+    pub seed: u64,
+    pub max_turns: usize,
+    pub threads: usize,
+    pub results: Vec<GameResult>,
+}
+
+impl Tournament {
+    pub fn new(bots: Vec<String>, maps: Vec<Map>, seed: u64) -> Self {
+        Self {
+            bots,
+            maps,
+            seed,
+            max_turns: crate::protocol::MAX_TURNS,
+            threads: 1,
+            results: vec![],
+        }
+    }
+
+    pub fn score(&self, bot: usize) -> f32 {
+        self.results
+            .iter()
+            .filter(|result| result.bots.contains(&bot))
+            .map(|result| result.score(bot))
+            .sum()
+    }
+
+    pub fn has_played(&self, a: usize, b: usize) -> bool {
+        self.results
+            .iter()
+            .any(|result| result.bots == [a, b] || result.bots == [b, a])
+    }
+
+    // Plays each pairing on every map with both player ids. Every game gets
+    // its own seeds, so that a tournament is reproducible.
+    pub fn play(&mut self, pairings: &[(usize, usize)]) -> Result<(), String> {
+        let mut games = vec![];
+        for (a, b) in pairings.iter() {
+            for map in 0..self.maps.len() {
+                games.push(([*a, *b], map));
+                games.push(([*b, *a], map));
+            }
+        }
+        let first = self.results.len();
+        let results = run_parallel(games.len(), self.threads, |i| -> Result<_, String> {
+            let (bots, map) = games[i];
+            let seed = self.seed.wrapping_add(2 * (first + i) as u64);
+            let mut bot0 = new_bot(&self.bots[bots[0]], seed)?;
+            let mut bot1 = new_bot(&self.bots[bots[1]], seed + 1)?;
+            let (_, winner) = play_bots(
+                &self.maps[map],
+                [bot0.as_mut(), bot1.as_mut()],
+                self.max_turns,
+            );
+            Ok(GameResult { bots, map, winner })
+        });
+        for result in results {
+            self.results.push(result?);
+        }
+        Ok(())
+    }
+
+    pub fn round_robin(&mut self) -> Result<(), String> {
+        let n = self.bots.len();
+        let pairings: Vec<_> = (0..n)
+            .flat_map(|a| (a + 1..n).map(move |b| (a, b)))
+            .collect();
+        self.play(&pairings)
+    }
+
+    // Pairs the bots in score order, each with the next one it has not met
+    // yet if any. The last one sits out when they are odd.
+    pub fn swiss_pairings(&self) -> Vec<(usize, usize)> {
+        let mut order: Vec<_> = (0..self.bots.len()).collect();
+        order.sort_by(|a, b| self.score(*b).partial_cmp(&self.score(*a)).unwrap());
+        let mut pairings = vec![];
+        while order.len() > 1 {
+            let a = order.remove(0);
+            let index = order
+                .iter()
+                .position(|b| !self.has_played(a, *b))
+                .unwrap_or(0);
+            pairings.push((a, order.remove(index)));
+        }
+        pairings
+    }
+
+    pub fn swiss(&mut self, rounds: usize) -> Result<(), String> {
+        for _ in 0..rounds {
+            let pairings = self.swiss_pairings();
+            self.play(&pairings)?;
+        }
+        Ok(())
+    }
+
+    pub fn rating_table(&self) -> String {
+        let ratings = ratings(self.bots.len(), &self.results);
+        let mut order: Vec<_> = (0..self.bots.len()).collect();
+        order.sort_by(|a, b| ratings[*b].elo.partial_cmp(&ratings[*a].elo).unwrap());
+        let width = self.bots.iter().map(|bot| bot.len()).max().unwrap_or(0);
+        let mut ret = format!(
+            "{:>4}  {:<width$}  {:>5}  {:>6}  {:>5}  {:>6}\n",
+            "Rank",
+            "Bot",
+            "Elo",
+            "95% CI",
+            "Games",
+            "Score",
+            width = width
+        );
+        for (rank, bot) in order.iter().enumerate() {
+            let rating = &ratings[*bot];
+            ret += &format!(
+                "{:>4}  {:<width$}  {:>5.0}  {:>6}  {:>5}  {:>6.1}\n",
+                rank + 1,
+                self.bots[*bot],
+                rating.elo,
+                format!("±{:.0}", rating.ci),
+                rating.games,
+                rating.score,
+                width = width
+            );
+        }
+        ret
+    }
+}
+
+// -----------------------------------------------------------------------
+// Ratings
+// -----------------------------------------------------------------------
+// Elo ratings fitted on all the games at once (Bradley-Terry). Each bot also
+// draws virtual games against a bot rated ELO_BASE, so that ratings stay
+// finite for bots winning or losing everything.
+pub const ELO_BASE: f32 = 1500.0;
+pub const ELO_PRIOR_DRAWS: f32 = 1.0;
+pub const ELO_ITERATIONS: usize = 100;
+// Normal quantile of the 95% confidence intervals
+pub const ELO_CI_Z: f32 = 1.96;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rating {
+    pub elo: f32,
+    // Half width of the 95% confidence interval
+    pub ci: f32,
+    pub games: usize,
+    pub score: f32,
+}
+
+pub fn elo_expected(elo: f32, opp_elo: f32) -> f32 {
+    1.0 / (1.0 + 10f32.powf((opp_elo - elo) / 400.0))
+}
+
+pub fn ratings(nb_bots: usize, results: &[GameResult]) -> Vec<Rating> {
+    let k = 10f32.ln() / 400.0;
+    let mut elos = vec![ELO_BASE; nb_bots];
+    // Expected score and its variance, summed on the games of bot
+    let expected = |elos: &[f32], bot: usize| {
+        let p = elo_expected(elos[bot], ELO_BASE);
+        let mut ret = (ELO_PRIOR_DRAWS * p, ELO_PRIOR_DRAWS * p * (1.0 - p));
+        for result in results.iter().filter(|result| result.bots.contains(&bot)) {
+            let opp = if result.bots[0] == bot {
+                result.bots[1]
+            } else {
+                result.bots[0]
+            };
+            let p = elo_expected(elos[bot], elos[opp]);
+            ret.0 += p;
+            ret.1 += p * (1.0 - p);
+        }
+        ret
+    };
+    let scores: Vec<f32> = (0..nb_bots)
+        .map(|bot| {
+            results
+                .iter()
+                .filter(|result| result.bots.contains(&bot))
+                .map(|result| result.score(bot))
+                .sum()
+        })
+        .collect();
+    // Newton steps on each rating in turn
+    for _ in 0..ELO_ITERATIONS {
+        for bot in 0..nb_bots {
+            let (expected, variance) = expected(&elos, bot);
+            elos[bot] += (scores[bot] + 0.5 * ELO_PRIOR_DRAWS - expected) / (k * variance);
+        }
+    }
+    (0..nb_bots)
+        .map(|bot| {
+            let (_, variance) = expected(&elos, bot);
+            Rating {
+                elo: elos[bot],
+                ci: ELO_CI_Z / (k * variance.sqrt()),
+                games: results
+                    .iter()
+                    .filter(|result| result.bots.contains(&bot))
+                    .count(),
+                score: scores[bot],
+            }
+        })
+        .collect()
+}
+
+// Tests -----------------------------------------------------------------
+#[test]
+fn test_ratings() {
+    let game = |bots: [usize; 2], winner: Option<usize>| GameResult {
+        bots,
+        map: 0,
+        winner,
+    };
+    let mut results = vec![];
+    for _ in 0..10 {
+        results.push(game([0, 1], Some(0)));
+        results.push(game([1, 0], Some(1)));
+        results.push(game([1, 2], Some(0)));
+        results.push(game([2, 1], None));
+        results.push(game([0, 2], Some(0)));
+    }
+    let ranked = ratings(3, &results);
+    assert!(ranked[0].elo > ranked[1].elo && ranked[1].elo > ranked[2].elo);
+    assert_eq!(ranked[0].games, 30);
+    assert_eq!(ranked[1].score, 15.0);
+    assert!(ranked.iter().all(|rating| rating.ci > 0.0));
+    // Even bots stay at the base
+    let even = ratings(2, &[game([0, 1], Some(0)), game([0, 1], Some(1))]);
+    assert!((even[0].elo - ELO_BASE).abs() < 0.1);
+    assert!((even[1].elo - ELO_BASE).abs() < 0.1);
+}
+
+#[test]
+fn test_tournament() {
+    let mut rng = rand::rngs::StdRng::seed_from_u64(0);
+    let maps = vec![Map::random(9, 9, MATCH_ISLAND_RATIO, &mut rng)];
+    let bots = ["random", "spammer", "runner"];
+    let mut tournament = Tournament::new(bots.iter().map(|s| s.to_string()).collect(), maps, 1);
+    tournament.max_turns = 20;
+    tournament.threads = 2;
+    tournament.round_robin().unwrap();
+    // Each pairing with both ids
+    assert_eq!(tournament.results.len(), 6);
+    assert!(tournament.has_played(0, 2));
+    let played_as_0 = |bot| {
+        tournament
+            .results
+            .iter()
+            .filter(|r| r.bots[0] == bot)
+            .count()
+    };
+    assert!((0..3).all(|bot| played_as_0(bot) == 2));
+    assert!(tournament
+        .rating_table()
+        .lines()
+        .nth(1)
+        .unwrap()
+        .starts_with("   1"));
+
+    let mut swiss = Tournament::new(tournament.bots.clone(), tournament.maps.clone(), 1);
+    swiss.max_turns = 20;
+    swiss.swiss(2).unwrap();
+    assert_eq!(swiss.results.len(), 4);
+    // The second round pairs bots that have not met yet
+    assert_ne!(swiss.results[0].bots, swiss.results[2].bots);
+    assert_ne!(swiss.results[0].bots, swiss.results[3].bots);
+}
@@ -0,0 +1,46 @@
+// Tournaments between bots, printing their rating table:
+//   arena robin <seed> <nb maps> <bot>...
+//   arena swiss <rounds> <seed> <nb maps> <bot>...
+// Games run on every core, and stay quiet unless OOC_LOG is set.
+use ocean_of_code::arena::*;
+
+fn run_tool(args: &[String]) -> Result<(), String> {
+    let arg = |i: usize| args.get(i).ok_or_else(|| "Missing argument".to_string());
+    let number = |i: usize| -> Result<usize, String> {
+        arg(i)?
+            .parse()
+            .map_err(|_| format!("Bad number: {}", args[i]))
+    };
+    let (rounds, first) = match arg(0)?.as_str() {
+        "robin" => (None, 1),
+        "swiss" => (Some(number(1)?), 2),
+        tool => return Err(format!("Unknown tool: {}", tool)),
+    };
+    let seed = number(first)? as u64;
+    let maps = map_pool(seed, number(first + 1)?);
+    let bots: Vec<String> = args[first + 2..].to_vec();
+    if bots.len() < 2 {
+        return Err("Two bots at least are needed".to_string());
+    }
+    let mut tournament = Tournament::new(bots, maps, seed);
+    tournament.threads = std::thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(1);
+    match rounds {
+        None => tournament.round_robin()?,
+        Some(rounds) => tournament.swiss(rounds)?,
+    }
+    print!("{}", tournament.rating_table());
+    Ok(())
+}
+
+fn main() {
+    if std::env::var("OOC_LOG").is_err() {
+        std::env::set_var("OOC_LOG", "off");
+    }
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if let Err(e) = run_tool(&args) {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+}
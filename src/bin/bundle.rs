@@ -0,0 +1,280 @@
+// Bundles the library and the bot into the single file CodinGame accepts:
+//   bundle [--nolog] [--no-check] [output file]
+// The library modules but the tool ones are inlined, tests, comments and
+// indentation are stripped, and the result is built against the rand crate of
+// our build.
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+const LIB_NAME: &str = "ocean_of_code";
+// Marks in lib.rs the modules only the local tools use
+const TOOL_ATTR: &str = "#[cfg(feature = \"tools\")]";
+// CodinGame rejects larger sources
+const CODINGAME_MAX_SIZE: usize = 100_000;
+
+// -----------------------------------------------------------------------
+// Source filtering
+// -----------------------------------------------------------------------
+// Drops #[test] functions and #[cfg(test)] items, which all end on a line
+// with a closing brace at column 0 or, for imports, on their first line
+fn strip_tests(src: &str) -> String {
+    let mut ret = vec![];
+    let mut lines = src.lines();
+    while let Some(line) = lines.next() {
+        let attr = line.trim();
+        if attr == "#[test]" || attr == "#[cfg(test)]" {
+            for line in lines.by_ref() {
+                if line == "}" || (line.starts_with("use ") && line.ends_with(';')) {
+                    break;
+                }
+            }
+            continue;
+        }
+        if line.starts_with("// Tests ---") {
+            continue;
+        }
+        ret.push(line);
+    }
+    ret.join("\n") + "\n"
+}
+
+// Drops comments, indentation and blank lines, leaving string literals alone
+fn minify(src: &str) -> String {
+    let chars: Vec<char> = src.chars().collect();
+    let mut ret = String::new();
+    let mut line = String::new();
+    let mut i = 0;
+    // Closing delimiter of the string literal we are in, if any
+    let mut string: Option<String> = None;
+    let flush = |line: &mut String, ret: &mut String| {
+        let trimmed = line.trim_end();
+        if !trimmed.is_empty() {
+            ret.push_str(trimmed);
+            ret.push('\n');
+        }
+        line.clear();
+    };
+    while i < chars.len() {
+        let c = chars[i];
+        if let Some(end) = &string {
+            if c == '\\' && end == "\"" {
+                line.push(c);
+                line.push(chars[i + 1]);
+                i += 2;
+                continue;
+            }
+            let end_len = end.chars().count();
+            if chars[i..].iter().take(end_len).collect::<String>() == *end {
+                line.push_str(end);
+                i += end_len;
+                string = None;
+                continue;
+            }
+            if c == '\n' {
+                ret.push_str(&line);
+                ret.push('\n');
+                line.clear();
+            } else {
+                line.push(c);
+            }
+            i += 1;
+            continue;
+        }
+        let prev_ident = i > 0 && (chars[i - 1].is_alphanumeric() || chars[i - 1] == '_');
+        match c {
+            '\n' => flush(&mut line, &mut ret),
+            ' ' | '\t' if line.is_empty() => (),
+            '/' if chars.get(i + 1) == Some(&'/') => {
+                while i + 1 < chars.len() && chars[i + 1] != '\n' {
+                    i += 1;
+                }
+            }
+            '"' => {
+                line.push(c);
+                string = Some("\"".to_string());
+            }
+            'r' if !prev_ident && matches!(chars.get(i + 1), Some('#') | Some('"')) => {
+                let hashes = chars[i + 1..].iter().take_while(|c| **c == '#').count();
+                if chars.get(i + 1 + hashes) == Some(&'"') {
+                    let start: String = chars[i..i + 2 + hashes].iter().collect();
+                    line.push_str(&start);
+                    string = Some("\"".to_string() + &"#".repeat(hashes));
+                    i += 2 + hashes;
+                    continue;
+                }
+                line.push(c);
+            }
+            // Char literals, told apart from lifetimes
+            '\'' if chars.get(i + 1) == Some(&'\\') || chars.get(i + 2) == Some(&'\'') => {
+                let end = (i + 2..chars.len()).find(|j| chars[*j] == '\'').unwrap();
+                line.extend(&chars[i..=end]);
+                i = end + 1;
+                continue;
+            }
+            c => line.push(c),
+        }
+        i += 1;
+    }
+    flush(&mut line, &mut ret);
+    ret
+}
+
+// -----------------------------------------------------------------------
+// Bundling
+// -----------------------------------------------------------------------
+fn read(path: &Path) -> Result<String, String> {
+    fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+fn bundle(src_dir: &Path, nolog: bool) -> Result<String, String> {
+    let lib = strip_tests(&read(&src_dir.join("lib.rs"))?);
+    let main = strip_tests(&read(&src_dir.join("main.rs"))?);
+
+    let mut ret = "#![allow(dead_code)]\n".to_string();
+    let mut tool = false;
+    for line in lib.lines() {
+        if line == TOOL_ATTR {
+            tool = true;
+            continue;
+        }
+        match line.strip_prefix("pub mod ") {
+            Some(name) => {
+                let name = name.trim_end_matches(';');
+                if !tool {
+                    let src = strip_tests(&read(&src_dir.join(format!("{}.rs", name)))?);
+                    ret += &format!("pub mod {} {{\n{}}}\n", name, src);
+                }
+            }
+            None => {
+                ret += line;
+                ret += "\n";
+            }
+        }
+        tool = false;
+    }
+    // Exported macros are already in scope at the crate root
+    for line in main.lines() {
+        if line != format!("use {}::parse_input;", LIB_NAME) {
+            ret += &line.replace(&format!("{}::", LIB_NAME), "crate::");
+            ret += "\n";
+        }
+    }
+    if nolog {
+        ret = ret.replace("cfg!(feature = \"nolog\")", "true");
+    }
+    Ok("// Generated by the bundle tool from src/, do not edit\n".to_string() + &minify(&ret))
+}
+
+// -----------------------------------------------------------------------
+// Check
+// -----------------------------------------------------------------------
+// The rand crate of our last build, with the directory of its dependencies
+fn find_rand(target_dir: &Path) -> Result<(PathBuf, PathBuf), String> {
+    let mut found = vec![];
+    for profile in ["debug", "release"].iter() {
+        let deps = target_dir.join(profile).join("deps");
+        let entries = match fs::read_dir(&deps) {
+            Ok(entries) => entries,
+            Err(_) => continue,
+        };
+        for entry in entries.flatten() {
+            let name = entry.file_name().to_string_lossy().to_string();
+            if name.starts_with("librand-") && name.ends_with(".rlib") {
+                let modified = entry.metadata().and_then(|m| m.modified()).ok();
+                found.push((modified, entry.path(), deps.clone()));
+            }
+        }
+    }
+    found.sort();
+    match found.pop() {
+        Some((_, rlib, deps)) => Ok((rlib, deps)),
+        None => Err("No rand build found, run cargo build first".to_string()),
+    }
+}
+
+fn check(path: &Path, target_dir: &Path) -> Result<(), String> {
+    let (rlib, deps) = find_rand(target_dir)?;
+    let out_dir = target_dir.join("bundle-check");
+    let output = Command::new("rustc")
+        .args(["--edition", "2018", "--crate-type", "bin"])
+        .arg("--out-dir")
+        .arg(&out_dir)
+        .arg("-L")
+        .arg(format!("dependency={}", deps.display()))
+        .arg("--extern")
+        .arg(format!("rand={}", rlib.display()))
+        .arg(path)
+        .output()
+        .map_err(|e| format!("rustc: {}", e))?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(String::from_utf8_lossy(&output.stderr).to_string())
+    }
+}
+
+fn run(args: &[String]) -> Result<(), String> {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let target_dir = match std::env::var("CARGO_TARGET_DIR") {
+        Ok(dir) => PathBuf::from(dir),
+        Err(_) => root.join("target"),
+    };
+    let nolog = args.iter().any(|arg| arg == "--nolog");
+    let no_check = args.iter().any(|arg| arg == "--no-check");
+    let output = match args.iter().find(|arg| !arg.starts_with("--")) {
+        Some(path) => PathBuf::from(path),
+        None => target_dir.join("codingame.rs"),
+    };
+
+    let src = bundle(&root.join("src"), nolog)?;
+    fs::write(&output, &src).map_err(|e| format!("{}: {}", output.display(), e))?;
+    eprintln!("{}: {} characters", output.display(), src.chars().count());
+    if src.chars().count() > CODINGAME_MAX_SIZE {
+        eprintln!(
+            "Warning: over the {} characters CodinGame accepts",
+            CODINGAME_MAX_SIZE
+        );
+    }
+    if !no_check {
+        check(&output, &target_dir)?;
+        eprintln!("{}: compiles", output.display());
+    }
+    Ok(())
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if let Err(e) = run(&args) {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+}
+
+// Tests -----------------------------------------------------------------
+#[test]
+fn test_strip_tests() {
+    let src =
+        "use a;\n#[cfg(test)]\nuse b;\n\n// Tests ---\n#[test]\nfn t() {\n    f();\n}\nfn f() {}\n";
+    assert_eq!(strip_tests(src), "use a;\n\nfn f() {}\n");
+}
+
+#[test]
+fn test_minify() {
+    let src = "// Comment\nfn f<'a>(s: &'a str) -> char {\n    let url = \"http://x\"; // Trailing\n    let raw = r#\"  \"a\"\n  // kept\"#;\n\n    '\\''\n}\n";
+    assert_eq!(
+        minify(src),
+        "fn f<'a>(s: &'a str) -> char {\nlet url = \"http://x\";\nlet raw = r#\"  \"a\"\n  // kept\"#;\n'\\''\n}\n"
+    );
+}
+
+#[test]
+fn test_bundle() {
+    let src = bundle(&Path::new(env!("CARGO_MANIFEST_DIR")).join("src"), true).unwrap();
+    assert!(src.contains("pub mod ai {"));
+    assert!(!src.contains("pub mod replay"));
+    assert!(!src.contains("pub mod bot"));
+    assert!(!src.contains("feature = \"tools\""));
+    assert!(!src.contains("#[test]"));
+    assert!(!src.contains(LIB_NAME));
+}
@@ -0,0 +1,41 @@
+// Local match and replay tools:
+//   replay match <seed> <replay file> [max turns] [bot 0] [bot 1]
+//   replay html <replay file> <html file>
+use ocean_of_code::bot::*;
+use ocean_of_code::geometry::Map;
+use ocean_of_code::protocol::MAX_TURNS;
+use ocean_of_code::replay::*;
+use rand::SeedableRng;
+
+fn run_tool(args: &[String]) -> Result<(), String> {
+    let arg = |i: usize| args.get(i).ok_or_else(|| "Missing argument".to_string());
+    match arg(0)?.as_str() {
+        "match" => {
+            let seed: u64 = arg(1)?.parse().map_err(|_| "Bad seed".to_string())?;
+            let max_turns = match args.get(3) {
+                Some(n) => n.parse().map_err(|_| "Bad turn count".to_string())?,
+                None => MAX_TURNS,
+            };
+            let mut rng = rand::rngs::StdRng::seed_from_u64(seed);
+            let map = Map::random(MATCH_MAP_SIZE, MATCH_MAP_SIZE, MATCH_ISLAND_RATIO, &mut rng);
+            let name = |i: usize| args.get(i).map(|s| s.as_str()).unwrap_or("ai");
+            let mut bots = [new_bot(name(4), seed)?, new_bot(name(5), seed + 1)?];
+            let [bot0, bot1] = &mut bots;
+            let (replay, _) = play_bots(&map, [bot0.as_mut(), bot1.as_mut()], max_turns);
+            std::fs::write(arg(2)?, replay.to_string()).map_err(|e| e.to_string())
+        }
+        "html" => {
+            let html = Replay::load(arg(1)?)?.to_html()?;
+            std::fs::write(arg(2)?, html).map_err(|e| e.to_string())
+        }
+        tool => Err(format!("Unknown tool: {}", tool)),
+    }
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if let Err(e) = run_tool(&args) {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+}
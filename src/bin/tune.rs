@@ -0,0 +1,61 @@
+// Tunes the AI configuration by self-play, saving the best one found at the
+// start and after each improvement:
+//   tune <seed> <generations> <output file> [start config file]
+// Games run on every core, and stay quiet unless OOC_LOG is set.
+use ocean_of_code::ai::AiConfig;
+use ocean_of_code::tuner::*;
+
+fn run_tool(args: &[String]) -> Result<(), String> {
+    let arg = |i: usize| args.get(i).ok_or_else(|| "Missing argument".to_string());
+    let seed: u64 = arg(0)?.parse().map_err(|_| "Bad seed".to_string())?;
+    let generations: usize = arg(1)?
+        .parse()
+        .map_err(|_| "Bad generation count".to_string())?;
+    let output = arg(2)?;
+    let config = match args.get(3) {
+        Some(path) => AiConfig::load(path)?,
+        None => AiConfig::new(),
+    };
+    let mut tuner = Tuner::new(config, seed);
+    let save =
+        |config: &AiConfig| std::fs::write(output, config.to_string()).map_err(|e| e.to_string());
+    save(&tuner.best)?;
+    tuner.threads = std::thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(1);
+    for _ in 0..generations {
+        let generation = tuner.step();
+        let changes: Vec<_> = generation
+            .changes
+            .iter()
+            .map(|(key, value)| format!("{}={}", key, value))
+            .collect();
+        let confirmation = match generation.confirmation {
+            Some(score) => format!(", then {:.2}", score),
+            None => String::new(),
+        };
+        eprintln!(
+            "Generation {}: {} scores {:.2}{}{}",
+            tuner.generation,
+            changes.join(" "),
+            generation.score,
+            confirmation,
+            if generation.accepted { ", kept" } else { "" }
+        );
+        if generation.accepted {
+            save(&tuner.best)?;
+        }
+    }
+    Ok(())
+}
+
+fn main() {
+    if std::env::var("OOC_LOG").is_err() {
+        std::env::set_var("OOC_LOG", "off");
+    }
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if let Err(e) = run_tool(&args) {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+}
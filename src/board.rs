@@ -0,0 +1,138 @@
+use crate::game::*;
+use crate::geometry::*;
+#[cfg(test)]
+use crate::tracking::*;
+
+// =======================================================================
+// Board
+// =======================================================================
+// Debug view of the game in a single grid, printed each turn when
+// OOC_VERBOSITY is 1 (plain text) or 2 (ANSI colours):
+//   @ us   o our trail   x island   m our mine   * blast (ours yellow)
+//   ! likely opponent mine   ?/, opponent candidate, likely/unlikely
+// In colour, the opponent candidates are shaded red by likelihood.
+pub const BOARD_DANGER_MIN: f32 = 0.25;
+pub const BOARD_SHADES: [u8; 4] = [52, 88, 124, 160];
+
+pub struct BoardCell {
+    pub c: char,
+    pub fg: u8,
+    pub bg: Option<u8>,
+}
+
+impl Game {
+    pub fn board_cell(&self, pos: &Pos, weight: f32, danger: f32) -> BoardCell {
+        let bg = if self.map.get(pos.y, pos.x) {
+            Some(238)
+        } else if weight > 0.0 {
+            let shade = ((weight * BOARD_SHADES.len() as f32).ceil() as usize).max(1);
+            Some(BOARD_SHADES[shade - 1])
+        } else {
+            None
+        };
+        let ours = [self.last_turn.torpedo, self.last_turn.trigger];
+        let (c, fg) = if *pos == self.me.pos {
+            ('@', 46)
+        } else if ours.contains(&Some(*pos)) {
+            ('*', 226)
+        } else if self.opp.blasts.contains(pos) {
+            ('*', 196)
+        } else if self.me.mines.contains(pos) {
+            ('m', 51)
+        } else if self.map.get(pos.y, pos.x) {
+            ('x', 244)
+        } else if self.me.forbidden_map.get(pos.y, pos.x) {
+            ('o', 34)
+        } else if danger >= BOARD_DANGER_MIN {
+            ('!', 208)
+        } else if weight >= 0.5 {
+            ('?', 231)
+        } else if weight > 0.0 {
+            (',', 231)
+        } else {
+            ('.', 240)
+        };
+        BoardCell { c, fg, bg }
+    }
+
+    pub fn board(&self, color: bool) -> String {
+        let (h, w) = (self.map.h, self.map.w);
+        let weights = self.opp.weights(&self.map);
+        let max_weight = weights.iter().cloned().fold(0.0, f32::max);
+        let danger = self.opp.mines.danger();
+        let v_border = |x: usize| {
+            x > 0 && self.map.sector(&Pos { y: 0, x }) != self.map.sector(&Pos { y: 0, x: x - 1 })
+        };
+        let h_border = |y: usize| {
+            y > 0 && self.map.sector(&Pos { y, x: 0 }) != self.map.sector(&Pos { y: y - 1, x: 0 })
+        };
+
+        let mut lines = vec![];
+        let mut axis = "   ".to_string();
+        let mut separator = "   ".to_string();
+        for x in 0..w {
+            if v_border(x) {
+                axis.push(' ');
+                separator.push('+');
+            }
+            axis += &format!("{:<2}", x);
+            separator += "--";
+        }
+        lines.push(axis);
+        for y in 0..h {
+            if h_border(y) {
+                lines.push(separator.clone());
+            }
+            let mut line = format!("{:>2} ", y);
+            for x in 0..w {
+                if v_border(x) {
+                    line.push('|');
+                }
+                let i = y * w + x;
+                let weight = if max_weight > 0.0 {
+                    weights[i] / max_weight
+                } else {
+                    0.0
+                };
+                let cell = self.board_cell(&Pos { y, x }, weight, danger[i]);
+                if color {
+                    line += &format!("\x1b[38;5;{}m", cell.fg);
+                    if let Some(bg) = cell.bg {
+                        line += &format!("\x1b[48;5;{}m", bg);
+                    }
+                    line += &format!("{} \x1b[0m", cell.c);
+                } else {
+                    line += &format!("{} ", cell.c);
+                }
+            }
+            lines.push(line);
+        }
+        lines.join("\n")
+    }
+}
+
+// Tests -----------------------------------------------------------------
+#[test]
+fn test_board() {
+    let mut map = Map::new(6, 6);
+    map.set(0, 5, true);
+    let mut game = Game::new(map, 0);
+    game.me.pos = Pos { y: 1, x: 1 };
+    game.me.forbidden_map.set(1, 1, true);
+    game.me.forbidden_map.set(1, 0, true);
+    game.opp.pos = OppPos::Exact(Pos { y: 4, x: 4 });
+    game.opp.blasts = vec![Pos { y: 3, x: 0 }];
+    let expected = [
+        "   0 1  2 3  4 5 ",
+        " 0 . . |. . |. x ",
+        " 1 o @ |. . |. . ",
+        "   ----+----+----",
+        " 2 . . |. . |. . ",
+        " 3 * . |. . |. . ",
+        "   ----+----+----",
+        " 4 . . |. . |? . ",
+        " 5 . . |. . |. . ",
+    ];
+    assert_eq!(game.board(false), expected.join("\n"));
+    assert!(game.board(true).contains("\x1b[48;5;160m"));
+}
@@ -0,0 +1,452 @@
+use crate::ai::*;
+use crate::game::*;
+use crate::geometry::*;
+use crate::protocol::*;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use std::marker::PhantomData;
+
+// =======================================================================
+// Bot
+// =======================================================================
+// A strategy the arena can play: it picks a start, then plays its orders on
+// the Game of each turn.
+pub trait Bot {
+    fn name(&self) -> String;
+
+    fn choose_start(&mut self, map: &Map) -> Pos;
+
+    fn plan_turn(&mut self, game: &mut Game);
+
+    // Sees the turn input, with the answer to the last orders, before planning
+    fn observe(&mut self, _game: &Game) {}
+}
+
+impl Bot for Ai {
+    fn name(&self) -> String {
+        "ai".to_string()
+    }
+
+    fn choose_start(&mut self, map: &Map) -> Pos {
+        Ai::choose_start(self, map)
+    }
+
+    fn plan_turn(&mut self, game: &mut Game) {
+        self.plan_actions(game);
+    }
+}
+
+// Bots the arena knows by name: the baselines to benchmark against, then
+// our AI. "ai:<file>" names our AI with the configuration in that file.
+pub const BOT_NAMES: [&str; 5] = ["random", "spammer", "runner", "hunter", "ai"];
+
+pub fn new_bot(name: &str, seed: u64) -> Result<Box<dyn Bot>, String> {
+    if let Some(path) = name.strip_prefix("ai:") {
+        return Ok(Box::new(Ai::with_config(seed, AiConfig::load(path)?)));
+    }
+    match name {
+        "random" => Ok(Box::new(RandomBot::new(seed))),
+        "spammer" => Ok(Box::new(SpammerBot::new(seed))),
+        "runner" => Ok(Box::new(RunnerBot::new(seed))),
+        "hunter" => Ok(Box::new(HunterBot::new(seed))),
+        "ai" => Ok(Box::new(Ai::with_seed(seed))),
+        name => Err(format!("Unknown bot: {}", name)),
+    }
+}
+
+// Baselines avoid moves leaving them less free cells than this, when they can
+pub const BASELINE_MIN_AREA: usize = 10;
+// Candidates left for the hunter to fire
+pub const HUNTER_MAX_CANDIDATES: usize = 9;
+
+// Legal moves, with the cell reached and the free cells left from it
+pub fn open_moves(game: &Game) -> Vec<(Direction, Pos, usize)> {
+    [Direction::N, Direction::E, Direction::S, Direction::W]
+        .iter()
+        .filter(|dir| game.can_move_to(dir).is_ok())
+        .map(|dir| {
+            let next = dir.apply(&game.me.pos).unwrap();
+            let mut forbidden = game.me.forbidden_map.clone();
+            forbidden.set(next.y, next.x, true);
+            (*dir, next, forbidden.flood_size(&next))
+        })
+        .collect()
+}
+
+// Moves leaving enough room, all of them when none does
+pub fn roomy_moves(game: &Game) -> Vec<(Direction, Pos, usize)> {
+    let moves = open_moves(game);
+    let max_area = moves.iter().map(|(_, _, area)| *area).max().unwrap_or(0);
+    let min_area = std::cmp::min(BASELINE_MIN_AREA, max_area);
+    moves
+        .into_iter()
+        .filter(|(_, _, area)| *area >= min_area)
+        .collect()
+}
+
+// Moves towards the closest candidate of the opponent, charging system, or
+// surfaces when stuck
+pub fn chase<R: Rng>(game: &mut Game, rng: &mut R, system: System) {
+    let mut moves = roomy_moves(game);
+    if moves.is_empty() {
+        game.surface();
+        return;
+    }
+    let candidates = game.opp.candidates(&game.map);
+    moves.shuffle(rng);
+    let (dir, _, _) = moves
+        .iter()
+        .min_by_key(|(_, next, _)| Ai::opp_dist(&candidates, next))
+        .unwrap();
+    game.move_to(dir, &system).unwrap();
+}
+
+// Fires at target unless we would be caught in the blast
+pub fn fire_at(game: &mut Game, target: Pos) {
+    if target.square_dist(&game.me.pos) > 1 {
+        let _ = game.torpedo(target);
+    }
+}
+
+// -----------------------------------------------------------------------
+// BaselineBot
+// -----------------------------------------------------------------------
+// How a baseline plays its turns, drawing from the rng of its bot
+pub trait Baseline {
+    const NAME: &'static str;
+
+    fn plan_turn(game: &mut Game, rng: &mut StdRng);
+}
+
+// Seeded bot playing a baseline from a random start
+pub struct BaselineBot<B> {
+    pub rng: StdRng,
+    baseline: PhantomData<B>,
+}
+
+impl<B: Baseline> BaselineBot<B> {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            rng: StdRng::seed_from_u64(seed),
+            baseline: PhantomData,
+        }
+    }
+}
+
+impl<B: Baseline> Bot for BaselineBot<B> {
+    fn name(&self) -> String {
+        B::NAME.to_string()
+    }
+
+    fn choose_start(&mut self, map: &Map) -> Pos {
+        map.rand_false_pos(&mut self.rng).unwrap()
+    }
+
+    fn plan_turn(&mut self, game: &mut Game) {
+        B::plan_turn(game, &mut self.rng);
+    }
+}
+
+// -----------------------------------------------------------------------
+// RandomBot
+// -----------------------------------------------------------------------
+// Moves in a random legal direction, charging a random system, and surfaces
+// when stuck
+pub struct Random;
+pub type RandomBot = BaselineBot<Random>;
+
+impl Baseline for Random {
+    const NAME: &'static str = "random";
+
+    fn plan_turn(game: &mut Game, rng: &mut StdRng) {
+        let dirs: Vec<_> = [Direction::N, Direction::E, Direction::S, Direction::W]
+            .iter()
+            .filter(|dir| game.can_move_to(dir).is_ok())
+            .cloned()
+            .collect();
+        if dirs.is_empty() {
+            game.surface();
+            return;
+        }
+        let dir = dirs[rng.gen_range(0, dirs.len())];
+        let systems = [
+            System::Torpedo,
+            System::Sonar,
+            System::Silence,
+            System::Mine,
+        ];
+        let system = systems[rng.gen_range(0, systems.len())];
+        game.move_to(&dir, &system).unwrap();
+    }
+}
+
+// -----------------------------------------------------------------------
+// SpammerBot
+// -----------------------------------------------------------------------
+// Chases the opponent charging only torpedoes, and fires each one at the
+// candidate closest to it, unless the blast would catch us
+pub struct Spammer;
+pub type SpammerBot = BaselineBot<Spammer>;
+
+impl Baseline for Spammer {
+    const NAME: &'static str = "spammer";
+
+    fn plan_turn(game: &mut Game, rng: &mut StdRng) {
+        chase(game, rng, System::Torpedo);
+        if game.me.torpedo > 0 {
+            return;
+        }
+        let me = game.me.pos;
+        let candidates = game.opp.candidates(&game.map);
+        let target = (0..game.map.h)
+            .flat_map(|y| (0..game.map.w).map(move |x| Pos { y, x }))
+            .filter(|pos| candidates.get(pos.y, pos.x) && game.in_torpedo_range(pos))
+            .min_by_key(|pos| pos.dist(&me));
+        if let Some(target) = target {
+            fire_at(game, target);
+        }
+    }
+}
+
+// -----------------------------------------------------------------------
+// RunnerBot
+// -----------------------------------------------------------------------
+// Never fires, and moves and silences where the opponent has the most cells
+// left to suspect
+pub struct Runner;
+pub type RunnerBot = BaselineBot<Runner>;
+
+impl Runner {
+    // Cells the opponent may think we are in once our orders are sent
+    pub fn exposure(game: &Game) -> usize {
+        game.seen_after_actions()
+            .candidates(game.map.h, game.map.w)
+            .nb_true()
+    }
+}
+
+impl Baseline for Runner {
+    const NAME: &'static str = "runner";
+
+    fn plan_turn(game: &mut Game, rng: &mut StdRng) {
+        let mut moves = roomy_moves(game);
+        if moves.is_empty() {
+            game.surface();
+            return;
+        }
+        moves.shuffle(rng);
+        let turn = moves
+            .iter()
+            .map(|(dir, _, _)| {
+                let mut turn = game.clone();
+                turn.move_to(dir, &System::Silence).unwrap();
+                turn
+            })
+            .max_by_key(Self::exposure)
+            .unwrap();
+        *game = turn;
+        if game.me.silence > 0 {
+            return;
+        }
+        let silences = [Direction::N, Direction::E, Direction::S, Direction::W]
+            .iter()
+            .flat_map(|dir| (1..=4).map(move |dist| (*dir, dist)))
+            .filter_map(|(dir, dist)| {
+                let mut turn = game.clone();
+                turn.silence(&dir, dist).ok()?;
+                let area = turn.me.forbidden_map.flood_size(&turn.me.pos);
+                Some((Self::exposure(&turn), area, turn))
+            })
+            .filter(|(_, area, _)| *area >= BASELINE_MIN_AREA)
+            .max_by_key(|(exposure, _, _)| *exposure);
+        if let Some((_, _, turn)) = silences {
+            *game = turn;
+        }
+    }
+}
+
+// -----------------------------------------------------------------------
+// HunterBot
+// -----------------------------------------------------------------------
+// Chases the opponent charging sonars first, each one splitting its
+// candidates as evenly as possible, and fires once they are few
+pub struct Hunter;
+pub type HunterBot = BaselineBot<Hunter>;
+
+impl Baseline for Hunter {
+    const NAME: &'static str = "hunter";
+
+    fn plan_turn(game: &mut Game, rng: &mut StdRng) {
+        let system = if game.me.sonar > 0 {
+            System::Sonar
+        } else {
+            System::Torpedo
+        };
+        chase(game, rng, system);
+        let candidates = game.opp.candidates(&game.map);
+        let nb_candidates = candidates.nb_true();
+        if game.me.sonar == 0 {
+            let split = (1..=NB_SECTORS)
+                .map(|sector| {
+                    let inside = candidates.nb_true_and(&game.map.sector_mask(sector));
+                    (sector, std::cmp::min(inside, nb_candidates - inside))
+                })
+                .max_by_key(|(_, split)| *split);
+            if let Some((sector, split)) = split {
+                if split > 0 {
+                    game.sonar(sector).unwrap();
+                }
+            }
+        }
+        if game.me.torpedo == 0 && nb_candidates <= HUNTER_MAX_CANDIDATES {
+            if let Some(target) = best_guess_shot(&game.map, &candidates) {
+                fire_at(game, target);
+            }
+        }
+    }
+}
+
+// -----------------------------------------------------------------------
+// ScriptedBot
+// -----------------------------------------------------------------------
+// Plays fixed orders from a fixed start, looping over the script. Orders the
+// game rejects are skipped.
+pub struct ScriptedBot {
+    pub start: Pos,
+    pub turns: Vec<Vec<Action>>,
+    pub next: usize,
+}
+
+impl ScriptedBot {
+    pub fn new(start: Pos, turns: Vec<Vec<Action>>) -> Self {
+        Self {
+            start,
+            turns,
+            next: 0,
+        }
+    }
+
+    // One line of orders per turn, separated by '|'
+    pub fn parse(start: Pos, lines: &[&str]) -> Result<Self, String> {
+        let turns = lines
+            .iter()
+            .map(|line| {
+                line.split('|')
+                    .map(|order| Action::parse_order(order.trim()))
+                    .collect::<Result<Vec<_>, _>>()
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self::new(start, turns))
+    }
+}
+
+impl Bot for ScriptedBot {
+    fn name(&self) -> String {
+        "scripted".to_string()
+    }
+
+    fn choose_start(&mut self, _map: &Map) -> Pos {
+        self.start
+    }
+
+    fn plan_turn(&mut self, game: &mut Game) {
+        if self.turns.is_empty() {
+            game.surface();
+            return;
+        }
+        for action in self.turns[self.next % self.turns.len()].iter() {
+            let _ = game.play(action);
+        }
+        self.next += 1;
+    }
+}
+
+// Tests -----------------------------------------------------------------
+#[test]
+fn test_bots() {
+    let map = Map::new(9, 9);
+    let mut scripted = ScriptedBot::parse(Pos { y: 0, x: 0 }, &["MOVE E TORPEDO"]).unwrap();
+    let mut random = new_bot("random", 1).unwrap();
+    let (replay, _) = crate::replay::play_bots(&map, [&mut scripted, random.as_mut()], 3);
+    assert_eq!(replay.turns.len(), 6);
+    for (i, orders) in replay.turns.iter().enumerate() {
+        if i % 2 == 0 {
+            assert_eq!(orders, "MOVE E TORPEDO");
+        } else {
+            assert!(orders.starts_with("MOVE "));
+        }
+    }
+    assert_eq!(replay.frames().unwrap()[5].pos[0], Pos { y: 0, x: 3 });
+    assert!(new_bot("nobody", 1).is_err());
+}
+
+#[test]
+fn test_bot_observe() {
+    // Moves east, recording where each turn input puts it
+    struct Recorder {
+        seen: Vec<Pos>,
+    }
+    impl Bot for Recorder {
+        fn name(&self) -> String {
+            "recorder".to_string()
+        }
+
+        fn choose_start(&mut self, _map: &Map) -> Pos {
+            Pos { y: 0, x: 0 }
+        }
+
+        fn plan_turn(&mut self, game: &mut Game) {
+            game.move_to(&Direction::E, &System::Torpedo).unwrap();
+        }
+
+        fn observe(&mut self, game: &Game) {
+            self.seen.push(game.me.pos);
+        }
+    }
+    let map = Map::new(9, 9);
+    let mut recorder = Recorder { seen: vec![] };
+    let mut scripted = ScriptedBot::parse(Pos { y: 8, x: 8 }, &["MOVE W TORPEDO"]).unwrap();
+    crate::replay::play_bots(&map, [&mut recorder, &mut scripted], 3);
+    let xs: Vec<_> = recorder.seen.iter().map(|pos| pos.x).collect();
+    assert_eq!(xs, vec![0, 1, 2]);
+}
+
+#[test]
+fn test_baseline_bots() {
+    use rand::SeedableRng;
+    let mut rng = rand::rngs::StdRng::seed_from_u64(2);
+    let map = Map::random(9, 9, 8, &mut rng);
+    for name in BOT_NAMES.iter().filter(|name| **name != "ai") {
+        let play = || {
+            let mut bot = new_bot(name, 1).unwrap();
+            let mut random = RandomBot::new(2);
+            crate::replay::play_bots(&map, [bot.as_mut(), &mut random], 30)
+        };
+        let (replay, winner) = play();
+        // Every order is legal, and a seed gives the same match
+        let frames = replay.frames().unwrap();
+        assert_eq!(frames.len(), replay.turns.len());
+        assert_eq!(play(), (replay, winner));
+        // The replay ends with the lives the match was won on
+        let life = frames.last().unwrap().life;
+        let expected = match life[0].cmp(&life[1]) {
+            std::cmp::Ordering::Greater => Some(0),
+            std::cmp::Ordering::Less => Some(1),
+            std::cmp::Ordering::Equal => None,
+        };
+        assert_eq!(winner, expected);
+    }
+
+    // The spammer fires at the closest candidate it can reach
+    let map = Map::new(9, 9);
+    let mut game = Game::new(map.clone(), 0);
+    game.me.pos = Pos { y: 4, x: 0 };
+    game.me.torpedo = 1;
+    let mut candidates = Map::new(9, 9);
+    candidates.set(4, 3, true);
+    candidates.set(4, 8, true);
+    game.opp.pos = crate::tracking::OppPos::Area(candidates);
+    SpammerBot::new(1).plan_turn(&mut game);
+    assert_eq!(game.actions[1], Action::Torpedo(Pos { y: 4, x: 3 }));
+}
@@ -0,0 +1,166 @@
+use crate::geometry::*;
+use crate::rules::*;
+
+// =======================================================================
+// Evaluation
+// =======================================================================
+// What makes a state good for us, shared by the heuristics and the search.
+// Counts are taken as log2 so that halving them always weighs the same.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EvalFeatures {
+    pub life_diff: f32,
+    // Cells the opponent can think of for us
+    pub hidden: f32,
+    // Cells we can think of for the opponent
+    pub opp_candidates: f32,
+    // Water we can still reach without surfacing
+    pub free_water: f32,
+    // Systems ready to be used
+    pub charged: f32,
+    // Part of the opponent candidates a mine of ours can hit
+    pub mine_coverage: f32,
+    // Mean distance to the opponent candidates, relative to the map size
+    pub opp_dist: f32,
+}
+
+impl EvalFeatures {
+    pub fn new(map: &Map, me: &SubState, seen: &Map, opp_candidates: &Map, opp_life: f32) -> Self {
+        let nb_opp = opp_candidates.nb_true();
+        let mut covered = 0;
+        let mut dist = 0;
+        for y in 0..map.h {
+            for x in 0..map.w {
+                if !opp_candidates.get(y, x) {
+                    continue;
+                }
+                let pos = Pos { y, x };
+                if me.mines.iter().any(|mine| mine.square_dist(&pos) <= 1) {
+                    covered += 1;
+                }
+                dist += me.pos.dist(&pos);
+            }
+        }
+        let nb_opp_f = std::cmp::max(1, nb_opp) as f32;
+        Self {
+            life_diff: me.life as f32 - opp_life,
+            hidden: (std::cmp::max(1, seen.nb_true()) as f32).log2(),
+            opp_candidates: nb_opp_f.log2(),
+            free_water: (1.0 + me.forbidden_map.usable_area(&me.pos) as f32).log2(),
+            charged: [me.torpedo, me.sonar, me.silence, me.mine]
+                .iter()
+                .filter(|cooldown| **cooldown == 0)
+                .count() as f32,
+            mine_coverage: covered as f32 / nb_opp_f,
+            opp_dist: dist as f32 / nb_opp_f / (map.h + map.w) as f32,
+        }
+    }
+}
+
+// Values every weight may take
+pub const EVAL_WEIGHT_RANGE: (f32, f32) = (-5.0, 5.0);
+
+// Weights are set in the AI configuration as "eval.<feature>"
+#[derive(Debug, Clone, PartialEq)]
+pub struct EvalWeights {
+    pub life_diff: f32,
+    pub hidden: f32,
+    pub opp_candidates: f32,
+    pub free_water: f32,
+    pub charged: f32,
+    pub mine_coverage: f32,
+    pub opp_dist: f32,
+}
+
+impl EvalWeights {
+    pub fn new() -> Self {
+        Self {
+            life_diff: 1.0,
+            hidden: 0.2,
+            opp_candidates: -0.1,
+            free_water: 0.25,
+            charged: 0.1,
+            mine_coverage: 0.3,
+            opp_dist: 0.0,
+        }
+    }
+
+    pub fn set(&mut self, key: &str, value: f32) -> Result<(), String> {
+        match key {
+            "life_diff" => self.life_diff = value,
+            "hidden" => self.hidden = value,
+            "opp_candidates" => self.opp_candidates = value,
+            "free_water" => self.free_water = value,
+            "charged" => self.charged = value,
+            "mine_coverage" => self.mine_coverage = value,
+            "opp_dist" => self.opp_dist = value,
+            key => return Err(format!("Unknown evaluation feature {}", key)),
+        }
+        Ok(())
+    }
+
+    pub fn values(&self) -> Vec<(&'static str, f32)> {
+        vec![
+            ("life_diff", self.life_diff),
+            ("hidden", self.hidden),
+            ("opp_candidates", self.opp_candidates),
+            ("free_water", self.free_water),
+            ("charged", self.charged),
+            ("mine_coverage", self.mine_coverage),
+            ("opp_dist", self.opp_dist),
+        ]
+    }
+
+    pub fn score(&self, features: &EvalFeatures) -> f32 {
+        self.life_diff * features.life_diff
+            + self.hidden * features.hidden
+            + self.opp_candidates * features.opp_candidates
+            + self.free_water * features.free_water
+            + self.charged * features.charged
+            + self.mine_coverage * features.mine_coverage
+            + self.opp_dist * features.opp_dist
+    }
+}
+
+impl Default for EvalWeights {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Parses "key=value" lines, blank lines and # comments being ignored
+pub fn parse_key_values(s: &str) -> Result<Vec<(String, f32)>, String> {
+    let mut ret = vec![];
+    for entry in s.lines() {
+        let entry = entry.split('#').next().unwrap().trim();
+        if entry.is_empty() {
+            continue;
+        }
+        let mut words = entry.splitn(2, '=');
+        let key = words.next().unwrap().trim();
+        let value = match words.next() {
+            Some(value) => value.trim(),
+            None => return Err(format!("Missing value for {}", key)),
+        };
+        let value = value
+            .parse::<f32>()
+            .map_err(|e| format!("{}: {}", key, e))?;
+        ret.push((key.to_string(), value));
+    }
+    Ok(ret)
+}
+
+// Tests -----------------------------------------------------------------
+#[test]
+fn test_eval() {
+    let map = Map::new(15, 15);
+    let me = SubState::new(&map, Pos { y: 7, x: 7 });
+    let mut opp_candidates = Map::new(15, 15);
+    opp_candidates.set(7, 8, true);
+    let mut seen = map.clone();
+    seen.invert();
+    let features = EvalFeatures::new(&map, &me, &seen, &opp_candidates, 4.0);
+    assert_eq!(features.life_diff, 2.0);
+    assert_eq!(features.opp_candidates, 0.0);
+    assert!((features.hidden - 225f32.log2()).abs() < 1e-6);
+    assert!((features.opp_dist - 1.0 / 30.0).abs() < 1e-6);
+}
@@ -0,0 +1,397 @@
+use crate::geometry::*;
+use crate::protocol::*;
+use crate::rules::*;
+use crate::tracking::*;
+use std::io;
+
+// -----------------------------------------------------------------------
+// LastTurn
+// -----------------------------------------------------------------------
+#[derive(Debug, Clone, PartialEq)]
+pub struct LastTurn {
+    pub opp_life: i32,
+    pub torpedo: Option<Pos>,
+    pub sonar: Option<usize>,
+    pub trigger: Option<Pos>,
+    pub mine: Option<Pos>,
+}
+
+impl LastTurn {
+    pub fn new(opp_life: i32) -> Self {
+        Self {
+            opp_life,
+            torpedo: None,
+            sonar: None,
+            trigger: None,
+            mine: None,
+        }
+    }
+}
+
+// -----------------------------------------------------------------------
+// Game
+// -----------------------------------------------------------------------
+#[derive(Debug, Clone, PartialEq)]
+pub struct Game {
+    // Static
+    pub map: Map,
+    pub my_id: usize,
+
+    // Dynamic
+    pub me: MePlayer,
+    pub opp: OppPlayer,
+    // Our turns played, this one included
+    pub turn: usize,
+
+    // Next action
+    pub actions: Vec<Action>,
+
+    // Last turn state
+    pub last_turn: LastTurn,
+
+    // Started once the turn input is read
+    pub clock: Clock,
+}
+
+// =======================================================================
+// Game rules
+// =======================================================================
+// Setup
+impl Game {
+    pub fn new(map: Map, my_id: usize) -> Self {
+        Self {
+            me: MePlayer::new(&map),
+            opp: OppPlayer::new(&map),
+            map,
+            my_id,
+            turn: 0,
+            actions: vec![],
+            last_turn: LastTurn::new(MAX_LIFE),
+            clock: Clock::start(FIRST_TURN_TIME),
+        }
+    }
+
+    pub fn start_at(&self, y: usize, x: usize) {
+        println!("{} {}", x, y);
+    }
+}
+
+// Gameplay
+impl Game {
+    pub fn update_opponent(&mut self, line: &str) {
+        self.opp.blasts.clear();
+        if line != "NA" {
+            self.opp.profile.start_turn(self.opp.torpedo == 0);
+            for action in parse_action_list(line) {
+                let candidates = self.opp.candidates(&self.map);
+                self.opp.profile.record(&action, &candidates);
+                match &action {
+                    Action::Mine(_) => {
+                        let candidates = self.opp.candidates(&self.map);
+                        self.opp.mines.add(&candidates, &self.map);
+                        self.opp.mine = cooldown::MINE;
+                    }
+                    Action::Trigger(pos) => {
+                        self.opp.mines.trigger(pos);
+                        self.opp.blasts.push(*pos);
+                    }
+                    // Cooldowns are estimated pessimistically: every move
+                    // charges every system
+                    Action::Move(_, _) => {
+                        self.opp.torpedo = self.opp.torpedo.saturating_sub(1);
+                        self.opp.sonar = self.opp.sonar.saturating_sub(1);
+                        self.opp.silence = self.opp.silence.saturating_sub(1);
+                        self.opp.mine = self.opp.mine.saturating_sub(1);
+                    }
+                    Action::Torpedo(pos) => {
+                        self.opp.torpedo = cooldown::TORPEDO;
+                        self.opp.blasts.push(*pos);
+                    }
+                    Action::Silence(_, _) => self.opp.silence = cooldown::SILENCE,
+                    // We know the answer the opponent got
+                    Action::Sonar(sector) => {
+                        self.opp.sonar = cooldown::SONAR;
+                        let inside = self.map.sector(&self.me.pos) == *sector;
+                        self.me.seen.apply_sector_answer(&self.map, *sector, inside);
+                    }
+                    _ => (),
+                }
+                self.opp.pos.apply(&action, &self.map);
+            }
+            if let OppPos::Exact(pos) = self.opp.pos {
+                self.opp.profile.locate(&pos, &self.map);
+            }
+        }
+        log!(Tracker, Debug, "Opponent position:\n{}", self.opp.pos);
+    }
+    // Our torpedo or mine went off before the opponent's last orders. Damage
+    // it dealt to itself is not taken into account.
+    pub fn apply_blast_result(&mut self, opp_orders: &str) {
+        let pos = match (self.last_turn.torpedo, self.last_turn.trigger) {
+            (Some(pos), None) | (None, Some(pos)) => pos,
+            _ => return,
+        };
+        let nb_surface = opp_orders
+            .split('|')
+            .filter(|order| order.trim().starts_with("SURFACE"))
+            .count() as i32;
+        let damage = self.last_turn.opp_life - self.opp.life - nb_surface;
+        let mut new_pos = None;
+        if let OppPos::Area(map) = &mut self.opp.pos {
+            let square_map = self.map.square(&pos);
+            match damage {
+                0 => map.eq_and_not(&square_map),
+                1 => {
+                    let mut ring_map = square_map;
+                    ring_map.set(pos.y, pos.x, false);
+                    map.eq_and(&ring_map);
+                }
+                _ => new_pos = Some(OppPos::Exact(pos)),
+            }
+            if new_pos.is_none() && map.nb_true() == 1 {
+                new_pos = Some(OppPos::Exact(map.first_true().unwrap()));
+            }
+        }
+        if let Some(pos) = new_pos {
+            self.opp.pos = pos;
+        }
+    }
+    // The answer is about the opponent position before its last orders
+    pub fn apply_sonar_result(&mut self, result: &str) {
+        let sector = match self.last_turn.sonar {
+            Some(sector) => sector,
+            None => return,
+        };
+        match result {
+            "Y" => self.opp.pos.apply_sector_answer(&self.map, sector, true),
+            "N" => self.opp.pos.apply_sector_answer(&self.map, sector, false),
+            _ => (),
+        }
+    }
+    pub fn sync(&mut self) {
+        let mut status = String::new();
+        io::stdin().read_line(&mut status).unwrap();
+        let mut sonar_result = String::new();
+        io::stdin().read_line(&mut sonar_result).unwrap();
+        let mut opponent_orders = String::new();
+        io::stdin().read_line(&mut opponent_orders).unwrap();
+        self.update(&status, sonar_result.trim(), opponent_orders.trim_end());
+    }
+
+    // Takes in the three lines of a turn input
+    pub fn update(&mut self, status: &str, sonar_result: &str, opponent_orders: &str) {
+        let inputs = status.split_whitespace().collect::<Vec<_>>();
+        let x = parse_input!(inputs[0], usize);
+        let y = parse_input!(inputs[1], usize);
+        self.me.pos = Pos { y, x };
+        self.me.life = parse_input!(inputs[2], i32);
+        self.opp.life = parse_input!(inputs[3], i32);
+        self.me.torpedo = parse_input!(inputs[4], usize);
+        self.me.sonar = parse_input!(inputs[5], usize);
+        self.me.silence = parse_input!(inputs[6], usize);
+        self.me.mine = parse_input!(inputs[7], usize);
+
+        // Update path map
+        self.me.forbidden_map.set(y, x, true);
+        self.clock = Clock::start(TURN_TIME);
+        self.turn += 1;
+
+        self.apply_sonar_result(sonar_result);
+        self.apply_blast_result(opponent_orders);
+        self.update_opponent(opponent_orders);
+
+        self.actions = vec![];
+        self.last_turn = LastTurn::new(self.opp.life);
+    }
+
+    pub fn can_move_to(&self, direction: &Direction) -> Result<(), ()> {
+        let me_pos = &self.me.pos;
+        let next_pos = direction.apply(me_pos)?;
+        if next_pos.x >= self.map.w || next_pos.y >= self.map.h {
+            return Err(());
+        }
+        if self.me.forbidden_map.get(next_pos.y, next_pos.x) {
+            return Err(());
+        }
+        Ok(())
+    }
+
+    // Actions update our position and cooldowns right away, so that the next
+    // actions of the turn are checked against them
+    pub fn move_to(&mut self, direction: &Direction, system: &System) -> Result<(), ()> {
+        self.can_move_to(direction)?;
+        self.me.pos = direction.apply(&self.me.pos)?;
+        self.me
+            .forbidden_map
+            .set(self.me.pos.y, self.me.pos.x, true);
+        let cooldown = match system {
+            System::Torpedo => &mut self.me.torpedo,
+            System::Sonar => &mut self.me.sonar,
+            System::Silence => &mut self.me.silence,
+            System::Mine => &mut self.me.mine,
+        };
+        *cooldown = cooldown.saturating_sub(1);
+        self.actions.push(Action::Move(*direction, *system));
+        Ok(())
+    }
+
+    pub fn surface(&mut self) {
+        self.actions
+            .push(Action::Surface(self.map.sector(&self.me.pos)));
+        self.me.forbidden_map.copy_from(&self.map);
+        self.me
+            .forbidden_map
+            .set(self.me.pos.y, self.me.pos.x, true);
+        self.me.life -= 1;
+    }
+
+    pub fn in_torpedo_range(&self, pos: &Pos) -> bool {
+        if pos.x >= self.map.w || pos.y >= self.map.h {
+            return false;
+        }
+        self.map.distances_from(&self.me.pos, 4)[pos.y * self.map.w + pos.x].is_some()
+    }
+
+    pub fn torpedo(&mut self, pos: Pos) -> Result<(), ()> {
+        if self.me.torpedo > 0 || !self.in_torpedo_range(&pos) {
+            return Err(());
+        }
+        self.me.torpedo = cooldown::TORPEDO;
+        // Our own blast hurts us as well
+        self.me.life -= blast_damage(&pos, &self.me.pos);
+        self.last_turn.torpedo = Some(pos);
+        self.actions.push(Action::Torpedo(pos));
+        Ok(())
+    }
+
+    pub fn sonar(&mut self, sector: usize) -> Result<(), ()> {
+        if self.me.sonar > 0 {
+            return Err(());
+        }
+        self.me.sonar = cooldown::SONAR;
+        self.last_turn.sonar = Some(sector);
+        self.actions.push(Action::Sonar(sector));
+        Ok(())
+    }
+
+    pub fn mine(&mut self, dir: &Direction) -> Result<(), ()> {
+        if self.me.mine > 0 {
+            return Err(());
+        }
+        let pos = dir.apply(&self.me.pos)?;
+        if pos.x >= self.map.w || pos.y >= self.map.h || self.map.get(pos.y, pos.x) {
+            return Err(());
+        }
+        if self.me.mines.contains(&pos) {
+            return Err(());
+        }
+        self.me.mine = cooldown::MINE;
+        self.me.mines.push(pos);
+        self.last_turn.mine = Some(pos);
+        self.actions.push(Action::Mine(*dir));
+        Ok(())
+    }
+
+    // Only one mine per turn, and not the one just dropped
+    pub fn trigger(&mut self, pos: Pos) -> Result<(), ()> {
+        if self.last_turn.trigger.is_some() || self.last_turn.mine == Some(pos) {
+            return Err(());
+        }
+        let index = match self.me.mines.iter().position(|mine| *mine == pos) {
+            Some(index) => index,
+            None => return Err(()),
+        };
+        self.me.mines.remove(index);
+        self.me.life -= blast_damage(&pos, &self.me.pos);
+        self.last_turn.trigger = Some(pos);
+        self.actions.push(Action::Trigger(pos));
+        Ok(())
+    }
+
+    pub fn silence(&mut self, dir: &Direction, dist: usize) -> Result<(), ()> {
+        if self.me.silence > 0 {
+            return Err(());
+        }
+        let mut me_pos = self.me.pos;
+        let mut path = vec![];
+        for _ in 0..dist {
+            me_pos = dir.apply(&me_pos)?;
+            if me_pos.x >= self.map.w || me_pos.y >= self.map.h {
+                return Err(());
+            }
+            if self.me.forbidden_map.get(me_pos.y, me_pos.x) {
+                return Err(());
+            }
+            path.push(me_pos);
+        }
+        // Only the final position is reported by the next sync
+        for pos in path {
+            self.me.forbidden_map.set(pos.y, pos.x, true);
+        }
+        self.me.pos = me_pos;
+        self.me.silence = cooldown::SILENCE;
+        self.actions.push(Action::Silence(*dir, dist));
+        Ok(())
+    }
+
+    pub fn turns_left(&self) -> usize {
+        MAX_TURNS.saturating_sub(self.turn)
+    }
+
+    // Cells a torpedo of the opponent may blast during its next turn, which
+    // may start with a move charging it
+    pub fn torpedo_threat(&self) -> Map {
+        if self.opp.torpedo > 1 {
+            return Map::new(self.map.h, self.map.w);
+        }
+        let targets = self.map.reach(&self.opp.candidates(&self.map), 1 + 4);
+        let mut ret = Map::new(self.map.h, self.map.w);
+        for y in 0..self.map.h {
+            for x in 0..self.map.w {
+                if targets.get(y, x) {
+                    ret.eq_or(&self.map.square(&Pos { y, x }));
+                }
+            }
+        }
+        ret
+    }
+
+    // Where the opponent may think we are once our orders are sent
+    pub fn seen_after_actions(&self) -> OppPos {
+        let mut seen = self.me.seen.clone();
+        for action in self.actions.iter() {
+            seen.apply(action, &self.map);
+        }
+        seen
+    }
+
+    // Plays one of our orders with the method giving it
+    pub fn play(&mut self, action: &Action) -> Result<(), ()> {
+        match action {
+            Action::Move(dir, system) => self.move_to(dir, system),
+            Action::Surface(_) => {
+                self.surface();
+                Ok(())
+            }
+            Action::Torpedo(pos) => self.torpedo(*pos),
+            Action::Sonar(sector) => self.sonar(*sector),
+            Action::Silence(dir, dist) => self.silence(dir, *dist),
+            Action::Mine(dir) => self.mine(dir),
+            Action::Trigger(pos) => self.trigger(*pos),
+        }
+    }
+
+    // Ends the turn, returning the orders to send
+    pub fn end_turn(&mut self) -> String {
+        self.me.seen = self.seen_after_actions();
+        self.actions
+            .iter()
+            .map(|a| a.to_string())
+            .collect::<Vec<_>>()
+            .join(" | ")
+    }
+
+    pub fn commit(&mut self) {
+        println!("{}", self.end_turn());
+    }
+}
@@ -0,0 +1,952 @@
+use rand::Rng;
+
+// =======================================================================
+// Defines
+// =======================================================================
+pub const MAP_NB_REGION: usize = 3;
+pub const SECTOR_SIZE: usize = 5;
+pub const MAP_SIDE_SIZE: usize = MAP_NB_REGION * SECTOR_SIZE;
+pub const NB_SECTORS: usize = 9;
+
+// -----------------------------------------------------------------------
+// Direction
+// -----------------------------------------------------------------------
+#[derive(Debug, Clone, PartialEq, Copy)]
+pub enum Direction {
+    N,
+    E,
+    S,
+    W,
+}
+impl std::fmt::Display for Direction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::N => 'N',
+                Self::E => 'E',
+                Self::S => 'S',
+                Self::W => 'W',
+            }
+        )
+    }
+}
+impl Direction {
+    pub fn apply(&self, pos: &Pos) -> Result<Pos, ()> {
+        match self {
+            Self::N => {
+                if pos.y == 0 {
+                    Err(())
+                } else {
+                    Ok(Pos {
+                        x: pos.x,
+                        y: pos.y - 1,
+                    })
+                }
+            }
+            Self::S => Ok(Pos {
+                x: pos.x,
+                y: pos.y + 1,
+            }),
+            Self::W => {
+                if pos.x == 0 {
+                    Err(())
+                } else {
+                    Ok(Pos {
+                        x: pos.x - 1,
+                        y: pos.y,
+                    })
+                }
+            }
+            Self::E => Ok(Pos {
+                x: pos.x + 1,
+                y: pos.y,
+            }),
+        }
+    }
+}
+impl Direction {
+    pub fn parse(s: &str) -> Self {
+        match s {
+            "N" => Self::N,
+            "E" => Self::E,
+            "S" => Self::S,
+            "W" => Self::W,
+            x => panic!("{}", x),
+        }
+    }
+}
+
+// =======================================================================
+// Tools
+// =======================================================================
+// -----------------------------------------------------------------------
+// NewMap
+// -----------------------------------------------------------------------
+pub fn nb_true_bits(n: u16) -> u8 {
+    let mut ret = 0;
+    for i in 0..16 {
+        if (n >> i) & 0x01 == 1 {
+            ret += 1;
+        }
+    }
+    ret
+}
+pub fn nb_false_bits(n: u16) -> u8 {
+    let mut ret = 0;
+    for i in 0..16 {
+        if (n >> i) & 0x01 == 0 {
+            ret += 1;
+        }
+    }
+    ret
+}
+pub static mut TRUE_BITS: [u8; 128] = [0; 128];
+pub static mut FALSE_BITS: [u8; 128] = [0; 128];
+// TODO Add init to main
+/// # Safety
+/// Writes the shared bit count tables, so must run before any reader.
+pub unsafe fn init_maps() {
+    for n in 0u16..128 {
+        TRUE_BITS[n as usize] = nb_true_bits(n);
+        FALSE_BITS[n as usize] = nb_false_bits(n);
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Copy)]
+pub struct NewMap {
+    pub data: [u16; MAP_SIDE_SIZE],
+}
+
+impl NewMap {
+    pub const fn new() -> Self {
+        Self {
+            data: [0; MAP_SIDE_SIZE],
+        }
+    }
+    pub fn set(&mut self, pos: Pos, value: bool) {
+        self.data[pos.y] &= !(1 << (pos.x as u16));
+        self.data[pos.y] |= (value as u16) << (pos.x as u16);
+    }
+    pub fn get(&self, pos: Pos) -> bool {
+        self.data[pos.y] & (1 << (pos.x as u16)) != 0
+    }
+    pub fn copy_from(&mut self, map: &NewMap) {
+        self.data.copy_from_slice(&map.data);
+    }
+
+    pub fn shift(&mut self, dir: &Direction, n: usize) {
+        match dir {
+            Direction::N => {
+                for i in 0..self.data.len() - n {
+                    self.data[i] = self.data[i + n];
+                }
+                for i in self.data.len() - n..self.data.len() {
+                    self.data[i] = 0;
+                }
+            }
+            Direction::S => {
+                for i in (n..self.data.len()).rev() {
+                    self.data[i] = self.data[i - n];
+                }
+                for i in 0..n {
+                    self.data[i] = 0;
+                }
+            }
+            Direction::E => self.data.iter_mut().for_each(|d| *d >>= n),
+            Direction::W => self.data.iter_mut().for_each(|d| *d <<= n),
+        }
+    }
+
+    pub fn ipos_shift(&mut self, pos_shift: IPos) -> Self {
+        let mut ret = *self;
+        if pos_shift.y < 0 {
+            ret.shift(&Direction::N, -pos_shift.y as usize);
+        } else if pos_shift.y > 0 {
+            ret.shift(&Direction::S, pos_shift.y as usize);
+        }
+        if pos_shift.x < 0 {
+            ret.shift(&Direction::W, -pos_shift.x as usize);
+        } else if pos_shift.x > 0 {
+            ret.shift(&Direction::E, pos_shift.x as usize);
+        }
+        ret
+    }
+
+    pub fn first_match(&self, value: bool) -> Result<Pos, ()> {
+        if value {
+            for (y, d) in self.data.iter().enumerate() {
+                if *d != 0x7F {
+                    for x in 0..self.data.len() {
+                        if (*d >> x) & 0x01 == 0 {
+                            return Ok(Pos { y, x });
+                        }
+                    }
+                }
+            }
+        } else {
+            for (y, d) in self.data.iter().enumerate() {
+                if *d != 0x00 {
+                    for x in 0..self.data.len() {
+                        if (*d >> x) & 0x01 == 1 {
+                            return Ok(Pos { y, x });
+                        }
+                    }
+                }
+            }
+        }
+        Err(())
+    }
+
+    pub fn count(&self, value: bool) -> usize {
+        unsafe {
+            if value {
+                self.data
+                    .iter()
+                    .map(|d| TRUE_BITS[*d as usize] as usize)
+                    .sum()
+            } else {
+                self.data
+                    .iter()
+                    .map(|d| FALSE_BITS[*d as usize] as usize)
+                    .sum()
+            }
+        }
+    }
+
+    pub fn compose(&self, map: &NewMap, map_offset: &Pos) -> NewMap {
+        let mut ret = NewMap::new();
+        for y in 0..MAP_SIDE_SIZE {
+            for x in 0..MAP_SIDE_SIZE {
+                let pos = Pos { x, y };
+                if self.get(pos) {
+                    ret |= map.clone().ipos_shift(pos.isub(map_offset));
+                }
+            }
+        }
+        ret
+    }
+}
+
+impl Default for NewMap {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl std::ops::BitOr for NewMap {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        let mut ret = Self::new();
+        for (i, d) in ret.data.iter_mut().enumerate() {
+            *d = self.data[i] | rhs.data[i];
+        }
+        ret
+    }
+}
+impl std::ops::BitOrAssign for NewMap {
+    fn bitor_assign(&mut self, rhs: Self) {
+        *self = *self | rhs
+    }
+}
+
+impl std::ops::BitAnd for NewMap {
+    type Output = Self;
+
+    fn bitand(self, rhs: Self) -> Self {
+        let mut ret = Self::new();
+        for (i, d) in ret.data.iter_mut().enumerate() {
+            *d = self.data[i] & rhs.data[i];
+        }
+        ret
+    }
+}
+impl std::ops::BitAndAssign for NewMap {
+    fn bitand_assign(&mut self, rhs: Self) {
+        *self = *self & rhs
+    }
+}
+
+impl std::ops::Not for NewMap {
+    type Output = Self;
+
+    fn not(mut self) -> Self {
+        self.data.iter_mut().for_each(|d| *d = !*d);
+        self
+    }
+}
+
+// -----------------------------------------------------------------------
+// Map
+// -----------------------------------------------------------------------
+#[derive(Debug, Clone, PartialEq)]
+pub struct Map {
+    pub h: usize,
+    pub w: usize,
+    // true means blocked
+    pub data: Box<[bool]>,
+}
+impl Map {
+    pub fn new(h: usize, w: usize) -> Self {
+        Self {
+            data: vec![false; w * h].into_boxed_slice(),
+            h,
+            w,
+        }
+    }
+    pub fn set(&mut self, y: usize, x: usize, value: bool) {
+        self.data[y * self.w + x] = value;
+    }
+    pub fn get(&self, y: usize, x: usize) -> bool {
+        self.data[y * self.w + x]
+    }
+    pub fn copy_from(&mut self, map: &Map) {
+        self.data.copy_from_slice(&map.data);
+    }
+
+    pub fn rand_true_pos<R: Rng>(&self, rng: &mut R) -> Option<Pos> {
+        let set: Vec<_> = (0..self.data.len()).filter(|i| self.data[*i]).collect();
+        if set.is_empty() {
+            return None;
+        }
+        let i = set[rng.gen_range(0, set.len())];
+        Some(Pos {
+            y: i / self.w,
+            x: i % self.w,
+        })
+    }
+
+    pub fn rand_false_pos<R: Rng>(&self, rng: &mut R) -> Result<Pos, ()> {
+        let free: Vec<_> = (0..self.data.len()).filter(|i| !self.data[*i]).collect();
+        if free.is_empty() {
+            return Err(());
+        }
+        let i = free[rng.gen_range(0, free.len())];
+        Ok(Pos {
+            y: i / self.w,
+            x: i % self.w,
+        })
+    }
+
+    // Islands grown from random seeds until they cover about 1/ratio of the
+    // map, drawn again until the water is in one piece
+    pub fn random<R: Rng>(h: usize, w: usize, ratio: usize, rng: &mut R) -> Self {
+        loop {
+            let mut map = Self::new(h, w);
+            let target = h * w / ratio;
+            while map.nb_true() < target {
+                let mut pos = map.rand_false_pos(rng).unwrap();
+                map.set(pos.y, pos.x, true);
+                for _ in 0..rng.gen_range(0, 6) {
+                    let dirs = [Direction::N, Direction::E, Direction::S, Direction::W];
+                    let next = match dirs[rng.gen_range(0, 4)].apply(&pos) {
+                        Ok(next) if next.x < w && next.y < h => next,
+                        _ => continue,
+                    };
+                    map.set(next.y, next.x, true);
+                    pos = next;
+                }
+            }
+            if let Some(start) = map.first_false() {
+                if map.flood_size(&start) + 1 == map.nb_false() {
+                    return map;
+                }
+            }
+        }
+    }
+
+    pub fn eq_and_not(&mut self, map: &Map) {
+        for (v, forbidden) in self.data.iter_mut().zip(map.data.iter()) {
+            *v = *v && !*forbidden;
+        }
+    }
+
+    pub fn eq_and(&mut self, map: &Map) {
+        for (v, allowed) in self.data.iter_mut().zip(map.data.iter()) {
+            if !*allowed {
+                *v = false;
+            }
+        }
+    }
+
+    pub fn eq_or(&mut self, map: &Map) {
+        for (v, allowed) in self.data.iter_mut().zip(map.data.iter()) {
+            *v = *v || *allowed;
+        }
+    }
+
+    pub fn invert(&mut self) {
+        self.data.iter_mut().for_each(|v| *v = !*v);
+    }
+
+    pub fn reset(&mut self) {
+        self.data.iter_mut().for_each(|v| *v = false);
+    }
+
+    pub fn sub_false_area_size(&self, start: &Pos) -> usize {
+        if self.get(start.y, start.x) {
+            return 0;
+        }
+        let mut map = Map::new(self.h, self.w);
+        map.set(start.y, start.x, true);
+
+        loop {
+            let mut changed = false;
+            for y in 0..self.h {
+                for x in 0..self.w {
+                    if !self.get(y, x) && !map.get(y, x) {
+                        let has_good_neigh = (x > 0 && map.get(y, x - 1))
+                            || (x < self.w - 1 && map.get(y, x + 1))
+                            || (y > 0 && map.get(y - 1, x))
+                            || (y < self.h - 1 && map.get(y + 1, x));
+                        if has_good_neigh {
+                            map.set(y, x, true);
+                            changed = true;
+                        }
+                    }
+                }
+            }
+
+            if !changed {
+                break;
+            }
+        }
+
+        map.data
+            .iter()
+            .fold(0, |acc, i| if *i { acc + 1 } else { acc })
+    }
+
+    pub fn eq_shift(&mut self, dir: &Direction) {
+        match dir {
+            Direction::N => {
+                for i in self.w..self.data.len() {
+                    self.data[i - self.w] = self.data[i];
+                }
+                for i in (self.data.len() - self.w)..self.data.len() {
+                    self.data[i] = false;
+                }
+            }
+            Direction::S => {
+                for i in (0..self.data.len() - self.w).rev() {
+                    self.data[i + self.w] = self.data[i];
+                }
+                for i in 0..self.w {
+                    self.data[i] = false;
+                }
+            }
+            Direction::E => {
+                for y in 0..self.h {
+                    for x in (0..self.w - 1).rev() {
+                        self.data[y * self.w + x + 1] = self.data[y * self.w + x];
+                    }
+                    self.data[y * self.w] = false;
+                }
+            }
+            Direction::W => {
+                for y in 0..self.h {
+                    for x in 0..self.w - 1 {
+                        self.data[y * self.w + x] = self.data[y * self.w + x + 1];
+                    }
+                    self.data[y * self.w + self.w - 1] = false;
+                }
+            }
+        }
+    }
+
+    pub fn sector(&self, pos: &Pos) -> usize {
+        let sector_w = self.w / 3;
+        let sector_h = self.h / 3;
+        let sector_x = pos.x / sector_w;
+        let sector_y = pos.y / sector_h;
+        sector_y * 3 + sector_x + 1
+    }
+
+    pub fn sector_mask(&self, sector: usize) -> Self {
+        let mut map = Self::new(self.h, self.w);
+        let sector_w = self.w / 3;
+        let sector_h = self.h / 3;
+        let sector_x = (sector - 1) % 3;
+        let sector_y = (sector - 1) / 3;
+        for y in sector_y * sector_h..(sector_y + 1) * sector_h {
+            for x in sector_x * sector_w..(sector_x + 1) * sector_w {
+                map.set(y, x, true);
+            }
+        }
+        map
+    }
+
+    pub fn torpedo_mask(&self, pos: &Pos) -> Self {
+        let mut map = Self::new(self.h, self.w);
+        for y in 0..map.h as isize {
+            for x in 0..map.w as isize {
+                if dist1(pos.y as isize - y, pos.x as isize - x) <= 4 {
+                    map.set(y as usize, x as usize, true);
+                }
+            }
+        }
+        map
+    }
+
+    // Path lengths from start through the free cells, up to max_dist
+    pub fn distances_from(&self, start: &Pos, max_dist: usize) -> Vec<Option<usize>> {
+        let mut ret = vec![None; self.h * self.w];
+        ret[start.y * self.w + start.x] = Some(0);
+        let mut front = vec![*start];
+        for dist in 1..=max_dist {
+            let mut next_front = vec![];
+            for pos in front.iter() {
+                for next in self.free_neighbours(pos) {
+                    let i = next.y * self.w + next.x;
+                    if ret[i].is_none() {
+                        ret[i] = Some(dist);
+                        next_front.push(next);
+                    }
+                }
+            }
+            front = next_front;
+        }
+        ret
+    }
+
+    // Cells within dist steps of the sources, through the free cells
+    pub fn reach(&self, sources: &Map, dist: usize) -> Map {
+        let mut ret = sources.clone();
+        let mut front: Vec<_> = (0..self.h)
+            .flat_map(|y| (0..self.w).map(move |x| Pos { y, x }))
+            .filter(|pos| sources.get(pos.y, pos.x))
+            .collect();
+        for _ in 0..dist {
+            let mut next_front = vec![];
+            for pos in front.iter() {
+                for next in self.free_neighbours(pos) {
+                    if !ret.get(next.y, next.x) {
+                        ret.set(next.y, next.x, true);
+                        next_front.push(next);
+                    }
+                }
+            }
+            front = next_front;
+        }
+        ret
+    }
+
+    // Adds every cell reachable with a silence from the current ones
+    pub fn silence_expand(&mut self, islands: &Map) {
+        let mut map = self.clone();
+        for y in 0..self.h {
+            for x in 0..self.w {
+                if !self.get(y, x) {
+                    continue;
+                }
+                for dir in [Direction::N, Direction::E, Direction::S, Direction::W].iter() {
+                    let mut pos = Pos { y, x };
+                    for _ in 0..4 {
+                        pos = match dir.apply(&pos) {
+                            Ok(pos) if pos.x < self.w && pos.y < self.h => pos,
+                            _ => break,
+                        };
+                        if islands.get(pos.y, pos.x) {
+                            break;
+                        }
+                        map.set(pos.y, pos.x, true);
+                    }
+                }
+            }
+        }
+        self.data.copy_from_slice(&map.data);
+    }
+
+    pub fn expand(&mut self, size: usize) {
+        let mut map = self.clone();
+        for _ in 0..size {
+            let mut tmp = map.clone();
+            for y in 0..self.h {
+                for x in 0..self.w {
+                    let has_good_neigh = (x > 0 && map.get(y, x - 1))
+                        || (x < self.w - 1 && map.get(y, x + 1))
+                        || (y > 0 && map.get(y - 1, x))
+                        || (y < self.h - 1 && map.get(y + 1, x));
+                    if has_good_neigh {
+                        tmp.set(y, x, true);
+                    }
+                }
+            }
+            map = tmp;
+        }
+        self.data.copy_from_slice(&map.data);
+    }
+
+    pub fn square(&self, pos: &Pos) -> Map {
+        let min_x = if pos.x > 0 { pos.x - 1 } else { 0 };
+        let min_y = if pos.y > 0 { pos.y - 1 } else { 0 };
+        let max_x = if pos.x < self.w - 1 { pos.x + 1 } else { pos.x };
+        let max_y = if pos.y < self.h - 1 { pos.y + 1 } else { pos.y };
+        let mut map = Map::new(self.h, self.w);
+        for y in min_y..=max_y {
+            for x in min_x..=max_x {
+                map.set(y, x, true);
+            }
+        }
+        map
+    }
+
+    pub fn nb_false(&self) -> usize {
+        self.data.iter().map(|v| (!*v) as usize).sum()
+    }
+
+    pub fn nb_true(&self) -> usize {
+        self.data.iter().map(|v| *v as usize).sum()
+    }
+
+    pub fn nb_true_and(&self, map: &Map) -> usize {
+        self.data
+            .iter()
+            .zip(map.data.iter())
+            .filter(|(v, other)| **v && **other)
+            .count()
+    }
+
+    pub fn first_false(&self) -> Option<Pos> {
+        for (i, v) in self.data.iter().enumerate() {
+            if !v {
+                return Some(Pos {
+                    y: i / self.w,
+                    x: i % self.w,
+                });
+            }
+        }
+        None
+    }
+
+    pub fn free_neighbours(&self, pos: &Pos) -> Vec<Pos> {
+        [Direction::N, Direction::E, Direction::S, Direction::W]
+            .iter()
+            .filter_map(|dir| dir.apply(pos).ok())
+            .filter(|next| next.x < self.w && next.y < self.h && !self.get(next.y, next.x))
+            .collect()
+    }
+
+    // Number of free cells connected to start, start excluded
+    pub fn flood_size(&self, start: &Pos) -> usize {
+        let mut seen = self.clone();
+        seen.set(start.y, start.x, true);
+        let mut stack = vec![*start];
+        let mut size = 0;
+        while let Some(pos) = stack.pop() {
+            for next in seen.free_neighbours(&pos) {
+                seen.set(next.y, next.x, true);
+                stack.push(next);
+                size += 1;
+            }
+        }
+        size
+    }
+
+    // pos may be an articulation point of the free cells: once there, only one
+    // of the areas it separates can still be used
+    pub fn usable_area(&self, pos: &Pos) -> usize {
+        let mut map = self.clone();
+        map.set(pos.y, pos.x, true);
+        map.free_neighbours(pos)
+            .iter()
+            .map(|next| 1 + map.flood_size(next))
+            .max()
+            .unwrap_or(0)
+    }
+
+    // Length of the longest self avoiding path starting from pos (which must
+    // already be blocked), capped to depth. Neighbours with the fewest exits
+    // are explored first so that long paths are found before the node budget
+    // runs out.
+    pub fn longest_path(&mut self, pos: &Pos, depth: usize, budget: &mut usize) -> usize {
+        if depth == 0 || *budget == 0 {
+            return 0;
+        }
+        *budget -= 1;
+        let mut nexts: Vec<_> = self
+            .free_neighbours(pos)
+            .into_iter()
+            .map(|next| (self.free_neighbours(&next).len(), next))
+            .collect();
+        nexts.sort_by_key(|(nb_exits, _)| *nb_exits);
+        let mut best = 0;
+        for (_, next) in nexts {
+            self.set(next.y, next.x, true);
+            let len = 1 + self.longest_path(&next, depth - 1, budget);
+            self.set(next.y, next.x, false);
+            if len > best {
+                best = len;
+                if best == depth {
+                    break;
+                }
+            }
+        }
+        best
+    }
+
+    pub fn first_true(&self) -> Option<Pos> {
+        for (i, v) in self.data.iter().enumerate() {
+            if *v {
+                return Some(Pos {
+                    y: i / self.w,
+                    x: i % self.w,
+                });
+            }
+        }
+        None
+    }
+}
+pub fn dist1(dy: isize, dx: isize) -> usize {
+    dy.unsigned_abs() + dx.unsigned_abs()
+}
+
+// Tests -----------------------------------------------------------------
+#[test]
+fn test_shift() {
+    let mut map_a = Map::new(3, 3);
+    map_a.set(1, 1, true);
+    let mut map_n = Map::new(3, 3);
+    map_n.set(0, 1, true);
+
+    map_a.eq_shift(&Direction::N);
+    assert_eq!(map_a, map_n);
+
+    let mut map_middle = Map::new(3, 3);
+    map_middle.set(1, 1, true);
+
+    map_a.eq_shift(&Direction::S);
+    assert_eq!(map_a, map_middle);
+
+    let mut map_e = Map::new(3, 3);
+    map_e.set(1, 2, true);
+
+    map_a.eq_shift(&Direction::E);
+    assert_eq!(map_a, map_e);
+
+    map_a.eq_shift(&Direction::W);
+    assert_eq!(map_a, map_middle);
+}
+#[test]
+fn test_and() {
+    let mut map_a = Map::new(3, 3);
+    map_a.set(1, 1, true);
+    map_a.set(0, 1, true);
+    let mut map_b = Map::new(3, 3);
+    map_b.set(0, 1, true);
+    map_b.set(1, 2, true);
+    let mut map_c = Map::new(3, 3);
+    map_c.set(0, 1, true);
+    let mut map_anb = map_a.clone();
+    map_anb.eq_and(&map_b);
+    assert_eq!(map_anb, map_c);
+}
+#[test]
+fn test_and_not() {
+    let mut map_a = Map::new(3, 3);
+    map_a.set(1, 1, true);
+    map_a.set(0, 1, true);
+    let mut map_b = Map::new(3, 3);
+    map_b.set(0, 1, true);
+    map_b.set(1, 2, true);
+    let mut map_c = Map::new(3, 3);
+    map_c.set(1, 1, true);
+    let mut map_anb = map_a.clone();
+    map_anb.eq_and_not(&map_b);
+    assert_eq!(map_anb, map_c);
+}
+#[test]
+fn test_or() {
+    let mut map_a = Map::new(3, 3);
+    map_a.set(1, 1, true);
+    map_a.set(0, 1, true);
+    let mut map_b = Map::new(3, 3);
+    map_b.set(0, 1, true);
+    map_b.set(1, 2, true);
+    let mut map_c = Map::new(3, 3);
+    map_c.set(1, 1, true);
+    map_c.set(0, 1, true);
+    map_c.set(1, 2, true);
+    let mut map_anb = map_a.clone();
+    map_anb.eq_or(&map_b);
+    assert_eq!(map_anb, map_c);
+}
+#[test]
+fn test_sub_false_area_size_small() {
+    let mut map = Map::new(3, 3);
+    map.set(1, 0, true);
+    assert_eq!(map.sub_false_area_size(&Pos { y: 0, x: 0 }), 8);
+    assert_eq!(map.sub_false_area_size(&Pos { y: 1, x: 1 }), 8);
+    map.set(1, 1, true);
+    assert_eq!(map.sub_false_area_size(&Pos { y: 0, x: 0 }), 7);
+    assert_eq!(map.sub_false_area_size(&Pos { y: 2, x: 1 }), 7);
+    map.set(1, 2, true);
+    assert_eq!(map.sub_false_area_size(&Pos { y: 0, x: 0 }), 3);
+    assert_eq!(map.sub_false_area_size(&Pos { y: 2, x: 1 }), 3);
+}
+#[test]
+fn test_sub_false_area_size() {
+    let size = 15;
+    let mut map_big = Map::new(size, size);
+    for i in 0..size {
+        map_big.set(size / 2, i, true);
+    }
+    assert_eq!(
+        map_big.sub_false_area_size(&Pos { y: 0, x: 0 }),
+        size / 2 * size
+    );
+    assert_eq!(
+        map_big.sub_false_area_size(&Pos {
+            y: size - 1,
+            x: size - 1
+        }),
+        size / 2 * size
+    );
+    map_big.set(1, 1, true);
+    map_big.set(1, 2, true);
+    assert_eq!(
+        map_big.sub_false_area_size(&Pos { y: 0, x: 0 }),
+        size / 2 * size - 2
+    );
+}
+
+impl std::fmt::Display for Map {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut ret = vec![];
+        for y in 0..self.h {
+            let mut tmp = vec![];
+            for x in 0..self.w {
+                if self.data[y * self.w + x] {
+                    tmp.push('x');
+                } else {
+                    tmp.push('.');
+                }
+            }
+            ret.push(tmp.into_iter().collect::<String>());
+        }
+        write!(f, "{}", ret.join("\n"))
+    }
+}
+
+// -----------------------------------------------------------------------
+// Pos
+// -----------------------------------------------------------------------
+#[derive(Debug, Clone, PartialEq, Copy)]
+pub struct Pos {
+    pub x: usize,
+    pub y: usize,
+}
+impl std::fmt::Display for Pos {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "[{};{}]", self.x, self.y)
+    }
+}
+
+impl std::ops::Sub for Pos {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self {
+        Pos {
+            x: self.x - rhs.x,
+            y: self.y - rhs.y,
+        }
+    }
+}
+
+impl Pos {
+    pub fn dist(&self, rhs: &Pos) -> usize {
+        ((self.x as isize - rhs.x as isize).abs() + (self.y as isize - rhs.y as isize).abs())
+            as usize
+    }
+    pub fn square_dist(&self, rhs: &Pos) -> usize {
+        std::cmp::max(
+            (self.x as isize - rhs.x as isize).abs(),
+            (self.y as isize - rhs.y as isize).abs(),
+        ) as usize
+    }
+    pub fn isub(&self, rhs: &Self) -> IPos {
+        IPos {
+            x: self.x as isize - rhs.x as isize,
+            y: self.y as isize - rhs.y as isize,
+        }
+    }
+}
+
+pub struct IPos {
+    pub x: isize,
+    pub y: isize,
+}
+
+// =======================================================================
+// Game defines
+// =======================================================================
+#[derive(Debug, Clone, PartialEq)]
+pub struct OffCenteredMap {
+    pub map: NewMap,
+    pub offset: Pos,
+}
+impl OffCenteredMap {
+    pub const fn new() -> Self {
+        Self {
+            map: NewMap::new(),
+            offset: Pos { y: 0, x: 0 },
+        }
+    }
+}
+
+impl Default for OffCenteredMap {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+// WARN These maps should never ever be modified except during the init phase
+pub static mut SECTOR_MASK: [NewMap; NB_SECTORS] = [NewMap::new(); NB_SECTORS];
+pub static mut TORPEDO_RANGE_MAP: OffCenteredMap = OffCenteredMap::new();
+pub static mut TORPEDO_SIDE_HIT_MAP: OffCenteredMap = OffCenteredMap::new();
+pub static mut SILENCE_RANGE_MAP: OffCenteredMap = OffCenteredMap::new();
+// TODO Call this in the main
+/// # Safety
+/// Writes the shared maps, so must run before any reader.
+pub unsafe fn init_game_maps() {
+    // Map sectors
+    for (i, map) in SECTOR_MASK.iter_mut().enumerate() {
+        let sector_x = i % 3;
+        let sector_y = i / 3;
+        for y in sector_y * SECTOR_SIZE..((sector_y + 1) * SECTOR_SIZE) {
+            for x in sector_x * SECTOR_SIZE..((sector_x + 1) * SECTOR_SIZE) {
+                map.set(Pos { x, y }, true);
+            }
+        }
+    }
+
+    // Torpedo range
+    TORPEDO_RANGE_MAP.offset = Pos { y: 4, x: 4 };
+    for y in 0..9 {
+        for x in 0..9 {
+            let pos = Pos { x, y };
+            if pos.dist(&TORPEDO_RANGE_MAP.offset) <= 4 {
+                TORPEDO_RANGE_MAP.map.set(pos, true);
+            }
+        }
+    }
+
+    // Torpedo side hit
+    TORPEDO_SIDE_HIT_MAP.offset = Pos { y: 1, x: 1 };
+    for y in 0..3 {
+        for x in 0..3 {
+            let pos = Pos { x, y };
+            if y != 1 || x != 1 {
+                TORPEDO_RANGE_MAP.map.set(pos, true);
+            }
+        }
+    }
+
+    // Silence
+    SILENCE_RANGE_MAP.offset = Pos { y: 1, x: 1 };
+    for i in 0..(4 * 2 + 1) {
+        SILENCE_RANGE_MAP.map.set(Pos { y: 4, x: i }, true);
+        SILENCE_RANGE_MAP.map.set(Pos { y: i, x: 4 }, true);
+    }
+}
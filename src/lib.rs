@@ -0,0 +1,50 @@
+// The CodinGame bot in main.rs and the tools in bin/ are thin binaries over
+// these modules.
+// The static tables of NewMap and PosData, the upcoming bitboard tracker, are
+// only written at init.
+#![allow(static_mut_refs)]
+// Failures carry no detail, the caller only needs to know the order is invalid
+#![allow(clippy::result_unit_err)]
+
+// =======================================================================
+// Macros
+// =======================================================================
+#[macro_export]
+macro_rules! parse_input {
+    ($x:expr, $t:ident) => {
+        $x.trim().parse::<$t>().unwrap()
+    };
+}
+
+// Writes to stderr when the channel logs at that level, see Log
+#[macro_export]
+macro_rules! log {
+    ($channel:ident, $level:ident, $($arg:tt)*) => {
+        if $crate::log::LogLevel::$level <= $crate::log::LOG_MAX_LEVEL
+            && $crate::log::log_enabled(
+                $crate::log::LogChannel::$channel,
+                $crate::log::LogLevel::$level,
+            )
+        {
+            eprintln!($($arg)*);
+        }
+    };
+}
+
+pub mod ai;
+#[cfg(feature = "tools")]
+pub mod arena;
+pub mod board;
+#[cfg(feature = "tools")]
+pub mod bot;
+pub mod eval;
+pub mod game;
+pub mod geometry;
+pub mod log;
+pub mod protocol;
+#[cfg(feature = "tools")]
+pub mod replay;
+pub mod rules;
+pub mod tracking;
+#[cfg(feature = "tools")]
+pub mod tuner;
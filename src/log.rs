@@ -0,0 +1,119 @@
+// =======================================================================
+// Log
+// =======================================================================
+// OOC_LOG sets the level of each channel, e.g. "planner=debug,parse=off", a
+// bare level setting every channel. Channels log at info by default.
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+pub enum LogLevel {
+    Off,
+    Error,
+    Info,
+    Debug,
+    Trace,
+}
+
+impl LogLevel {
+    pub fn parse(s: &str) -> Result<Self, String> {
+        match s {
+            "off" => Ok(LogLevel::Off),
+            "error" => Ok(LogLevel::Error),
+            "info" => Ok(LogLevel::Info),
+            "debug" => Ok(LogLevel::Debug),
+            "trace" => Ok(LogLevel::Trace),
+            s => Err(format!("Unknown log level: {}", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LogChannel {
+    Parse,
+    Tracker,
+    Planner,
+    Timing,
+}
+pub const LOG_CHANNELS: [(LogChannel, &str); 4] = [
+    (LogChannel::Parse, "parse"),
+    (LogChannel::Tracker, "tracker"),
+    (LogChannel::Planner, "planner"),
+    (LogChannel::Timing, "timing"),
+];
+
+// Most verbose level compiled in. Submission builds use the nolog feature,
+// which compiles every log out.
+pub const LOG_MAX_LEVEL: LogLevel = if cfg!(feature = "nolog") {
+    LogLevel::Off
+} else {
+    LogLevel::Trace
+};
+pub const LOG_DEFAULT_LEVEL: LogLevel = LogLevel::Info;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LogConfig {
+    pub levels: [LogLevel; LOG_CHANNELS.len()],
+}
+
+impl LogConfig {
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let mut levels = [LOG_DEFAULT_LEVEL; LOG_CHANNELS.len()];
+        for item in s
+            .split(',')
+            .map(|item| item.trim())
+            .filter(|item| !item.is_empty())
+        {
+            let mut parts = item.splitn(2, '=');
+            let (name, level) = match (parts.next(), parts.next()) {
+                (Some(level), None) => (None, LogLevel::parse(level)?),
+                (Some(name), Some(level)) => (Some(name.trim()), LogLevel::parse(level.trim())?),
+                _ => unreachable!(),
+            };
+            match name {
+                None => levels = [level; LOG_CHANNELS.len()],
+                Some(name) => {
+                    let i = LOG_CHANNELS
+                        .iter()
+                        .position(|(_, channel)| *channel == name)
+                        .ok_or(format!("Unknown log channel: {}", name))?;
+                    levels[i] = level;
+                }
+            }
+        }
+        Ok(Self { levels })
+    }
+
+    pub fn enabled(&self, channel: LogChannel, level: LogLevel) -> bool {
+        level != LogLevel::Off && level <= self.levels[channel as usize]
+    }
+}
+
+pub fn log_enabled(channel: LogChannel, level: LogLevel) -> bool {
+    static CONFIG: std::sync::OnceLock<LogConfig> = std::sync::OnceLock::new();
+    CONFIG
+        .get_or_init(
+            || match std::env::var("OOC_LOG").map(|s| LogConfig::parse(&s)) {
+                Ok(Ok(config)) => config,
+                Ok(Err(e)) => {
+                    // Our own channels are not configured yet
+                    eprintln!("Bad OOC_LOG, using the default levels: {}", e);
+                    LogConfig::parse("").unwrap()
+                }
+                Err(_) => LogConfig::parse("").unwrap(),
+            },
+        )
+        .enabled(channel, level)
+}
+
+// Tests -----------------------------------------------------------------
+#[test]
+fn test_log_config() {
+    let config = LogConfig::parse("").unwrap();
+    assert!(config.enabled(LogChannel::Planner, LogLevel::Info));
+    assert!(!config.enabled(LogChannel::Planner, LogLevel::Debug));
+
+    let config = LogConfig::parse("debug, parse=off,timing=trace").unwrap();
+    assert!(config.enabled(LogChannel::Planner, LogLevel::Debug));
+    assert!(!config.enabled(LogChannel::Parse, LogLevel::Error));
+    assert!(config.enabled(LogChannel::Timing, LogLevel::Trace));
+    assert!(LogConfig::parse("planner=loud").is_err());
+    assert!(LogConfig::parse("sonar=info").is_err());
+}
@@ -0,0 +1,300 @@
+use crate::geometry::*;
+use std::time::{Duration, Instant};
+
+// =======================================================================
+// Defines
+// =======================================================================
+pub mod cooldown {
+    pub const TORPEDO: usize = 3;
+    pub const SONAR: usize = 4;
+    pub const SILENCE: usize = 7;
+    pub const MINE: usize = 3;
+}
+pub const MAX_LIFE: i32 = 6;
+// Turns each player plays before the game is decided on life
+pub const MAX_TURNS: usize = 300;
+
+// -----------------------------------------------------------------------
+// System
+// -----------------------------------------------------------------------
+#[derive(Debug, Clone, PartialEq, Copy)]
+pub enum System {
+    Torpedo,
+    Sonar,
+    Silence,
+    Mine,
+}
+impl std::fmt::Display for System {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::Torpedo => "TORPEDO",
+                Self::Sonar => "SONAR",
+                Self::Silence => "SILENCE",
+                Self::Mine => "MINE",
+            }
+        )
+    }
+}
+impl System {
+    pub fn parse(s: &str) -> Self {
+        match s {
+            "TORPEDO" => Self::Torpedo,
+            "SONAR" => Self::Sonar,
+            "SILENCE" => Self::Silence,
+            "MINE" => Self::Mine,
+            s => panic!("{}", s),
+        }
+    }
+}
+
+// -----------------------------------------------------------------------
+// MeAction
+// -----------------------------------------------------------------------
+#[derive(Debug, Clone, PartialEq)]
+pub enum MeAction {
+    Move { dir: Direction, sys: System },
+    Surface,
+    Torpedo(Pos),
+    Sonar(usize),
+    Silence { dir: Direction, dist: usize },
+}
+impl std::fmt::Display for MeAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                MeAction::Move { dir, sys } => format!("MOVE {} {}", dir, sys),
+                MeAction::Surface => "SURFACE".to_string(),
+                MeAction::Torpedo(Pos { y, x }) => format!("TORPEDO {} {}", x, y),
+                MeAction::Sonar(sector) => format!("SONAR {}", sector),
+                MeAction::Silence { dir, dist } => format!("SILENCE {} {}", dir, dist),
+            }
+        )
+    }
+}
+
+impl MeAction {
+    pub fn to_opp_action(&self, current_sector: usize) -> OppAction {
+        match self {
+            MeAction::Move { dir, .. } => OppAction::Move(*dir),
+            MeAction::Surface => OppAction::Surface(current_sector),
+            MeAction::Torpedo(pos) => OppAction::Torpedo(*pos),
+            MeAction::Sonar(sector) => OppAction::Sonar(*sector),
+            MeAction::Silence { .. } => OppAction::Silence,
+        }
+    }
+}
+
+// -----------------------------------------------------------------------
+// OppAction
+// -----------------------------------------------------------------------
+#[derive(Debug, Clone, PartialEq)]
+pub enum OppAction {
+    Move(Direction),
+    Surface(usize),
+    Torpedo(Pos),
+    Sonar(usize),
+    Silence,
+}
+impl OppAction {
+    pub fn parse(s: &str) -> Self {
+        log!(Parse, Trace, "Action parse: {}", s);
+        let mut words: Vec<_> = s.split(' ').collect();
+        let cmd = words.remove(0);
+        match cmd {
+            "MOVE" => OppAction::Move(Direction::parse(words[0])),
+            "SURFACE" => OppAction::Surface(parse_input!(words[0], usize)),
+            "TORPEDO" => OppAction::Torpedo(Pos {
+                x: parse_input!(words[0], usize),
+                y: parse_input!(words[1], usize),
+            }),
+            "SONAR" => OppAction::Sonar(parse_input!(words[0], usize)),
+            "SILENCE" => OppAction::Silence,
+            x => panic!("{}", x),
+        }
+    }
+}
+
+// -----------------------------------------------------------------------
+// Action
+// -----------------------------------------------------------------------
+// TODO There should be a Me action and an Opp action because the parameter differ too much
+#[derive(Debug, Clone, PartialEq)]
+pub enum Action {
+    Move(Direction, System),
+    Surface(usize),
+    Torpedo(Pos),
+    Sonar(usize),
+    Silence(Direction, usize),
+    Mine(Direction),
+    Trigger(Pos),
+}
+impl std::fmt::Display for Action {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Action::Move(dir, sys) => format!("MOVE {} {}", dir, sys),
+                Action::Surface(_) => "SURFACE".to_string(),
+                Action::Torpedo(Pos { y, x }) => format!("TORPEDO {} {}", x, y),
+                Action::Sonar(sector) => format!("SONAR {}", sector),
+                Action::Silence(dir, dist) => format!("SILENCE {} {}", dir, dist),
+                Action::Mine(dir) => format!("MINE {}", dir),
+                Action::Trigger(Pos { y, x }) => format!("TRIGGER {} {}", x, y),
+            }
+        )
+    }
+}
+impl Action {
+    pub fn parse(s: &str) -> Self {
+        log!(Parse, Trace, "Action parse: {}", s);
+        let mut words: Vec<_> = s.split(' ').collect();
+        let cmd = words.remove(0);
+        match cmd {
+            "MOVE" => Action::Move(Direction::parse(words[0]), System::Torpedo),
+            "SURFACE" => Action::Surface(parse_input!(words[0], usize)),
+            "TORPEDO" => Action::Torpedo(Pos {
+                x: parse_input!(words[0], usize),
+                y: parse_input!(words[1], usize),
+            }),
+            "SONAR" => Action::Sonar(parse_input!(words[0], usize)),
+            "SILENCE" => Action::Silence(Direction::N, 0),
+            "MINE" => Action::Mine(Direction::N),
+            "TRIGGER" => Action::Trigger(Pos {
+                x: parse_input!(words[0], usize),
+                y: parse_input!(words[1], usize),
+            }),
+            x => panic!("{}", x),
+        }
+    }
+}
+
+impl Action {
+    // Parses one of our own orders, with the details the opponent does not get
+    pub fn parse_order(s: &str) -> Result<Self, String> {
+        let words: Vec<_> = s.split_whitespace().collect();
+        let arg = |i: usize| -> Result<usize, String> {
+            words
+                .get(i)
+                .and_then(|word| word.parse().ok())
+                .ok_or(format!("Bad order: {}", s))
+        };
+        let dir = |i: usize| match words.get(i) {
+            Some(&"N") => Ok(Direction::N),
+            Some(&"E") => Ok(Direction::E),
+            Some(&"S") => Ok(Direction::S),
+            Some(&"W") => Ok(Direction::W),
+            _ => Err(format!("Bad order: {}", s)),
+        };
+        Ok(match words.first() {
+            Some(&"MOVE") => {
+                let system = match words.get(2) {
+                    Some(&"TORPEDO") => System::Torpedo,
+                    Some(&"SONAR") => System::Sonar,
+                    Some(&"SILENCE") => System::Silence,
+                    Some(&"MINE") => System::Mine,
+                    _ => return Err(format!("Bad order: {}", s)),
+                };
+                Action::Move(dir(1)?, system)
+            }
+            Some(&"SURFACE") => Action::Surface(0),
+            Some(&"TORPEDO") => Action::Torpedo(Pos {
+                x: arg(1)?,
+                y: arg(2)?,
+            }),
+            Some(&"SONAR") => Action::Sonar(arg(1)?),
+            Some(&"SILENCE") => Action::Silence(dir(1)?, arg(2)?),
+            Some(&"MINE") => Action::Mine(dir(1)?),
+            Some(&"TRIGGER") => Action::Trigger(Pos {
+                x: arg(1)?,
+                y: arg(2)?,
+            }),
+            _ => return Err(format!("Bad order: {}", s)),
+        })
+    }
+
+    // The order as the opponent reads it, sector being where we surface
+    pub fn public(&self, sector: usize) -> String {
+        match self {
+            Action::Move(dir, _) => format!("MOVE {}", dir),
+            Action::Surface(_) => format!("SURFACE {}", sector),
+            Action::Silence(_, _) => "SILENCE".to_string(),
+            Action::Mine(_) => "MINE".to_string(),
+            action => action.to_string(),
+        }
+    }
+}
+
+pub fn parse_action_list(line: &str) -> Vec<Action> {
+    let mut ret = vec![];
+    for act_str in line.split('|') {
+        ret.push(Action::parse(act_str.trim()));
+    }
+    ret
+}
+
+// -----------------------------------------------------------------------
+// Clock
+// -----------------------------------------------------------------------
+// CodinGame answer time limits
+pub const FIRST_TURN_TIME: Duration = Duration::from_millis(1000);
+pub const TURN_TIME: Duration = Duration::from_millis(50);
+// Kept for the output and the scheduler, OOC_TIME_MARGIN_MS overrides it
+pub const TIME_MARGIN: Duration = Duration::from_millis(10);
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Clock {
+    pub start: Instant,
+    pub budget: Duration,
+    pub margin: Duration,
+}
+
+impl Clock {
+    pub fn start(budget: Duration) -> Self {
+        let margin = match std::env::var("OOC_TIME_MARGIN_MS").map(|ms| ms.parse()) {
+            Ok(Ok(ms)) => Duration::from_millis(ms),
+            Ok(Err(e)) => {
+                log!(
+                    Parse,
+                    Error,
+                    "Bad OOC_TIME_MARGIN_MS, using the default margin: {}",
+                    e
+                );
+                TIME_MARGIN
+            }
+            Err(_) => TIME_MARGIN,
+        };
+        Self {
+            start: Instant::now(),
+            budget,
+            margin,
+        }
+    }
+
+    pub fn deadline(&self) -> Instant {
+        self.start + self.budget.checked_sub(self.margin).unwrap_or_default()
+    }
+
+    // Deadline once share of the usable time is spent
+    pub fn deadline_at(&self, share: f32) -> Instant {
+        let usable = self.budget.checked_sub(self.margin).unwrap_or_default();
+        self.start + usable.mul_f32(share)
+    }
+
+    pub fn report(&self, what: &str) {
+        log!(
+            Timing,
+            Info,
+            "Time {}: {:?} used of {:?} ({:?} margin)",
+            what,
+            self.start.elapsed(),
+            self.budget,
+            self.margin
+        );
+    }
+}
@@ -0,0 +1,446 @@
+use crate::ai::*;
+use crate::bot::*;
+use crate::game::*;
+use crate::geometry::*;
+use crate::protocol::*;
+use crate::rules::*;
+use std::time::Duration;
+
+// =======================================================================
+// Match
+// =======================================================================
+// Local match: the rules referee the true state, and each player gets its own
+// Game fed with the lines CodinGame would send it.
+pub const MATCH_MAP_SIZE: usize = 15;
+pub const MATCH_ISLAND_RATIO: usize = 8;
+// Turn time given to the players, large enough for the search to be bounded
+// by its sample counts only, so that a seeded match is reproducible
+pub const MATCH_TURN_TIME: Duration = Duration::from_millis(1000);
+
+pub struct Match {
+    pub rules: Rules,
+    pub games: [Game; 2],
+    // Last orders of each player, as its opponent reads them
+    pub public: [String; 2],
+    // Answer to the last sonar of each player
+    pub sonar: [String; 2],
+    // Blasts of the last turn played
+    pub blasts: Vec<Pos>,
+}
+
+impl Match {
+    pub fn new(map: &Map, starts: [Pos; 2]) -> Self {
+        let mut games = [Game::new(map.clone(), 0), Game::new(map.clone(), 1)];
+        for (game, start) in games.iter_mut().zip(starts.iter()) {
+            game.me.pos = *start;
+        }
+        Self {
+            rules: Rules::new(
+                map.clone(),
+                [SubState::new(map, starts[0]), SubState::new(map, starts[1])],
+            ),
+            games,
+            public: ["NA".to_string(), "NA".to_string()],
+            sonar: ["NA".to_string(), "NA".to_string()],
+            blasts: vec![],
+        }
+    }
+
+    // Gives the player the input of its turn
+    pub fn sync(&mut self, player: usize) {
+        let sub = &self.rules.subs[player];
+        let status = format!(
+            "{} {} {} {} {} {} {} {}",
+            sub.pos.x,
+            sub.pos.y,
+            sub.life,
+            self.rules.subs[1 - player].life,
+            sub.torpedo,
+            sub.sonar,
+            sub.silence,
+            sub.mine
+        );
+        let sonar = std::mem::replace(&mut self.sonar[player], "NA".to_string());
+        self.games[player].update(&status, &sonar, &self.public[1 - player]);
+    }
+
+    // Plays the orders of the player on both its game and the rules. Invalid
+    // orders lose the game.
+    pub fn play(&mut self, player: usize, actions: &[Action]) -> Result<(), ()> {
+        let mut public = vec![];
+        self.blasts.clear();
+        for action in actions.iter() {
+            let sector = self.rules.map.sector(&self.rules.subs[player].pos);
+            let ret = self.games[player]
+                .play(action)
+                .and_then(|_| self.rules.play_action(player, action));
+            if ret.is_err() {
+                self.rules.subs[player].life = 0;
+                return Err(());
+            }
+            match action {
+                Action::Sonar(sector) => {
+                    let opp_sector = self.rules.map.sector(&self.rules.subs[1 - player].pos);
+                    self.sonar[player] = if opp_sector == *sector { "Y" } else { "N" }.to_string();
+                }
+                Action::Torpedo(pos) | Action::Trigger(pos) => self.blasts.push(*pos),
+                _ => (),
+            }
+            public.push(action.public(sector));
+        }
+        self.games[player].end_turn();
+        self.public[player] = public.join("|");
+        Ok(())
+    }
+
+    pub fn is_over(&self) -> bool {
+        self.rules.is_over()
+    }
+
+    // Winner of a finished match, None for a draw
+    pub fn winner(&self) -> Option<usize> {
+        let lives = [self.rules.subs[0].life, self.rules.subs[1].life];
+        if lives[0] > lives[1] {
+            Some(0)
+        } else if lives[1] > lives[0] {
+            Some(1)
+        } else {
+            None
+        }
+    }
+}
+
+// -----------------------------------------------------------------------
+// Replay
+// -----------------------------------------------------------------------
+// Text record of a match: the map size and rows, the start positions and the
+// orders of each turn, players alternating from player 0.
+//   15 15
+//   ...............
+//   start 3 4 10 12
+//   0 MOVE N TORPEDO
+//   1 MOVE E SILENCE | SILENCE E 2
+#[derive(Debug, Clone, PartialEq)]
+pub struct Replay {
+    pub map: Map,
+    pub starts: [Pos; 2],
+    pub turns: Vec<String>,
+}
+
+// State after one replayed turn
+pub struct Frame {
+    pub player: usize,
+    pub orders: String,
+    pub pos: [Pos; 2],
+    pub life: [i32; 2],
+    // Where each player thinks its opponent is, with weights up to 1
+    pub beliefs: [Vec<f32>; 2],
+    pub blasts: Vec<Pos>,
+    pub mines: [Vec<Pos>; 2],
+}
+
+impl Replay {
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let mut lines = s.lines();
+        let mut next = || lines.next().ok_or_else(|| "Truncated replay".to_string());
+        let size: Vec<usize> = next()?
+            .split_whitespace()
+            .map(|v| v.parse().map_err(|_| format!("Bad size: {}", v)))
+            .collect::<Result<_, _>>()?;
+        if size.len() != 2 {
+            return Err("Bad size line".to_string());
+        }
+        let (h, w) = (size[0], size[1]);
+        let mut map = Map::new(h, w);
+        for y in 0..h {
+            let row = next()?;
+            for (x, c) in row.chars().take(w).enumerate() {
+                map.set(y, x, c == 'x');
+            }
+        }
+        let starts: Vec<usize> = next()?
+            .split_whitespace()
+            .skip(1)
+            .map(|v| v.parse().map_err(|_| format!("Bad start: {}", v)))
+            .collect::<Result<_, _>>()?;
+        if starts.len() != 4 {
+            return Err("Bad start line".to_string());
+        }
+        let starts = [
+            Pos {
+                x: starts[0],
+                y: starts[1],
+            },
+            Pos {
+                x: starts[2],
+                y: starts[3],
+            },
+        ];
+        let mut turns = vec![];
+        for line in lines {
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            let (player, orders) = line.split_at(1);
+            if player != (turns.len() % 2).to_string() {
+                return Err(format!("Turn out of order: {}", line));
+            }
+            turns.push(orders.trim().to_string());
+        }
+        Ok(Self { map, starts, turns })
+    }
+
+    pub fn load(path: &str) -> Result<Self, String> {
+        let s = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+        Self::parse(&s)
+    }
+
+    // Plays the match again, giving the state after each turn
+    pub fn frames(&self) -> Result<Vec<Frame>, String> {
+        let mut game = Match::new(&self.map, self.starts);
+        let mut frames = vec![];
+        for (i, orders) in self.turns.iter().enumerate() {
+            let player = i % 2;
+            game.sync(player);
+            let actions = orders
+                .split('|')
+                .map(|order| Action::parse_order(order.trim()))
+                .collect::<Result<Vec<_>, _>>()?;
+            let played = game.play(player, &actions);
+            let belief = |game: &Game| {
+                let weights = game.opp.weights(&self.map);
+                let max = weights.iter().cloned().fold(0.0, f32::max);
+                weights
+                    .iter()
+                    .map(|w| if max > 0.0 { w / max } else { 0.0 })
+                    .collect::<Vec<_>>()
+            };
+            frames.push(Frame {
+                player,
+                orders: orders.clone(),
+                pos: [game.rules.subs[0].pos, game.rules.subs[1].pos],
+                life: [game.rules.subs[0].life, game.rules.subs[1].life],
+                beliefs: [belief(&game.games[0]), belief(&game.games[1])],
+                blasts: game.blasts.clone(),
+                mines: [
+                    game.rules.subs[0].mines.clone(),
+                    game.rules.subs[1].mines.clone(),
+                ],
+            });
+            if played.is_err() || game.is_over() {
+                break;
+            }
+        }
+        Ok(frames)
+    }
+
+    // Self-contained page scrubbing through the frames
+    pub fn to_html(&self) -> Result<String, String> {
+        let positions = |list: &[Pos]| {
+            list.iter()
+                .map(|pos| format!("[{},{}]", pos.x, pos.y))
+                .collect::<Vec<_>>()
+                .join(",")
+        };
+        let frames = self
+            .frames()?
+            .iter()
+            .map(|frame| {
+                let beliefs = frame
+                    .beliefs
+                    .iter()
+                    .map(|belief| {
+                        let cells: Vec<_> = belief.iter().map(|w| format!("{:.2}", w)).collect();
+                        format!("[{}]", cells.join(","))
+                    })
+                    .collect::<Vec<_>>();
+                format!(
+                    "{{\"player\":{},\"orders\":\"{}\",\"pos\":[{}],\"life\":[{},{}],\
+                     \"beliefs\":[{}],\"blasts\":[{}],\"mines\":[[{}],[{}]]}}",
+                    frame.player,
+                    frame.orders.replace(['\\', '"'], ""),
+                    positions(&frame.pos),
+                    frame.life[0],
+                    frame.life[1],
+                    beliefs.join(","),
+                    positions(&frame.blasts),
+                    positions(&frame.mines[0]),
+                    positions(&frame.mines[1])
+                )
+            })
+            .collect::<Vec<_>>();
+        let islands: Vec<_> = (0..self.map.h)
+            .flat_map(|y| (0..self.map.w).map(move |x| (y, x)))
+            .map(|(y, x)| if self.map.get(y, x) { "1" } else { "0" })
+            .collect();
+        let data = format!(
+            "{{\"h\":{},\"w\":{},\"islands\":[{}],\"starts\":[{}],\"frames\":[{}]}}",
+            self.map.h,
+            self.map.w,
+            islands.join(","),
+            positions(&self.starts),
+            frames.join(",\n")
+        );
+        Ok(REPLAY_HTML.replace("__DATA__", &data))
+    }
+}
+
+impl std::fmt::Display for Replay {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{} {}", self.map.h, self.map.w)?;
+        writeln!(f, "{}", self.map)?;
+        writeln!(
+            f,
+            "start {} {} {} {}",
+            self.starts[0].x, self.starts[0].y, self.starts[1].x, self.starts[1].y
+        )?;
+        for (i, orders) in self.turns.iter().enumerate() {
+            writeln!(f, "{} {}", i % 2, orders)?;
+        }
+        Ok(())
+    }
+}
+
+pub const REPLAY_HTML: &str = r##"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>Ocean of Code replay</title>
+<style>
+body { background: #111; color: #ddd; font-family: monospace; }
+#controls > * { margin-right: 8px; }
+#turn { width: 400px; }
+</style>
+</head>
+<body>
+<div id="controls">
+<button id="prev">&lt;</button><input id="turn" type="range" min="0" value="0"><button id="next">&gt;</button>
+<label>Belief of <select id="side"><option value="0">player 0 (green)</option><option value="1">player 1 (orange)</option></select></label>
+</div>
+<pre id="info"></pre>
+<svg id="board" xmlns="http://www.w3.org/2000/svg"></svg>
+<script>
+const data = __DATA__;
+const S = 32, COLORS = ["#3c3", "#f93"];
+const svg = document.getElementById("board");
+const turn = document.getElementById("turn");
+const side = document.getElementById("side");
+const info = document.getElementById("info");
+svg.setAttribute("width", data.w * S);
+svg.setAttribute("height", data.h * S);
+turn.max = data.frames.length - 1;
+
+function el(name, attrs, parent) {
+  const e = document.createElementNS("http://www.w3.org/2000/svg", name);
+  for (const k in attrs) e.setAttribute(k, attrs[k]);
+  (parent || svg).appendChild(e);
+  return e;
+}
+
+const cells = [];
+for (let y = 0; y < data.h; y++) {
+  for (let x = 0; x < data.w; x++) {
+    const island = data.islands[y * data.w + x];
+    el("rect", { x: x * S, y: y * S, width: S, height: S, fill: island ? "#7a6a48" : "#1d4e89", stroke: "#113" });
+    cells.push(el("rect", { x: x * S, y: y * S, width: S, height: S, fill: "#e33", "fill-opacity": 0 }));
+  }
+}
+for (let i = 1; i < 3; i++) {
+  el("line", { x1: i * data.w / 3 * S, y1: 0, x2: i * data.w / 3 * S, y2: data.h * S, stroke: "#ccc" });
+  el("line", { x1: 0, y1: i * data.h / 3 * S, x2: data.w * S, y2: i * data.h / 3 * S, stroke: "#ccc" });
+}
+const overlay = el("g", {});
+
+function center(p) { return [p[0] * S + S / 2, p[1] * S + S / 2]; }
+
+function draw() {
+  const i = Number(turn.value), f = data.frames[i], s = Number(side.value);
+  f.beliefs[s].forEach((w, j) => cells[j].setAttribute("fill-opacity", 0.7 * w));
+  overlay.innerHTML = "";
+  f.mines.forEach((mines, p) => mines.forEach(m => {
+    el("rect", { x: m[0] * S + S / 3, y: m[1] * S + S / 3, width: S / 3, height: S / 3, fill: COLORS[p] }, overlay);
+  }));
+  f.blasts.forEach(b => {
+    el("rect", { x: (b[0] - 1) * S, y: (b[1] - 1) * S, width: 3 * S, height: 3 * S, fill: "none", stroke: "#ff0", "stroke-width": 3 }, overlay);
+  });
+  f.pos.forEach((p, j) => {
+    const [cx, cy] = center(p);
+    el("circle", { cx: cx, cy: cy, r: S / 3, fill: COLORS[j], stroke: "#000" }, overlay);
+  });
+  info.textContent = "Turn " + i + ", player " + f.player + ": " + f.orders +
+    "\nLife: " + f.life[0] + " / " + f.life[1];
+}
+
+turn.oninput = draw;
+side.onchange = draw;
+document.getElementById("prev").onclick = () => { turn.value = Math.max(0, turn.value - 1); draw(); };
+document.getElementById("next").onclick = () => { turn.value = Math.min(turn.max, Number(turn.value) + 1); draw(); };
+document.onkeydown = e => {
+  if (e.key === "ArrowLeft") document.getElementById("prev").onclick();
+  if (e.key === "ArrowRight") document.getElementById("next").onclick();
+};
+draw();
+</script>
+</body>
+</html>
+"##;
+
+// Plays two bots against each other for at most max_turns each, returning
+// the record of the match and its winner
+pub fn play_bots(map: &Map, bots: [&mut dyn Bot; 2], max_turns: usize) -> (Replay, Option<usize>) {
+    let mut bots = bots;
+    let starts = [bots[0].choose_start(map), bots[1].choose_start(map)];
+    let mut game = Match::new(map, starts);
+    let mut turns = vec![];
+    'turns: for _ in 0..max_turns {
+        for (player, bot) in bots.iter_mut().enumerate() {
+            game.sync(player);
+            bot.observe(&game.games[player]);
+            let mut turn = game.games[player].clone();
+            turn.clock = Clock::start(MATCH_TURN_TIME);
+            bot.plan_turn(&mut turn);
+            turns.push(turn.end_turn());
+            if game.play(player, &turn.actions).is_err() || game.is_over() {
+                break 'turns;
+            }
+        }
+    }
+    let replay = Replay {
+        map: map.clone(),
+        starts,
+        turns,
+    };
+    (replay, game.winner())
+}
+
+// Plays our AI against itself, seeded
+pub fn play_match(map: &Map, seeds: [u64; 2], max_turns: usize) -> Replay {
+    let mut ais = [Ai::with_seed(seeds[0]), Ai::with_seed(seeds[1])];
+    let [ai0, ai1] = &mut ais;
+    play_bots(map, [ai0, ai1], max_turns).0
+}
+
+// Tests -----------------------------------------------------------------
+#[test]
+fn test_replay() {
+    use rand::SeedableRng;
+    let mut rng = rand::rngs::StdRng::seed_from_u64(0);
+    let map = Map::random(9, 9, MATCH_ISLAND_RATIO, &mut rng);
+    let start = map.first_false().unwrap();
+    assert_eq!(map.flood_size(&start) + 1, map.nb_false());
+
+    let replay = play_match(&map, [1, 2], 3);
+    assert_eq!(replay.turns.len(), 6);
+    assert_eq!(Replay::parse(&replay.to_string()), Ok(replay.clone()));
+    // Same seeds, same match
+    assert_eq!(play_match(&map, [1, 2], 3), replay);
+
+    let frames = replay.frames().unwrap();
+    assert_eq!(frames.len(), 6);
+    assert_eq!(frames[5].player, 1);
+    let html = replay.to_html().unwrap();
+    // The page script is JavaScript, whatever the Rust around it
+    assert!(!REPLAY_HTML.contains("pub const"));
+    assert!(html.contains("\"frames\":[{\"player\":0"));
+}
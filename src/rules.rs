@@ -0,0 +1,200 @@
+use crate::geometry::*;
+use crate::protocol::*;
+use crate::tracking::*;
+
+// =======================================================================
+// Rules
+// =======================================================================
+// Damage a blast centered on blast deals at pos
+pub fn blast_damage(blast: &Pos, pos: &Pos) -> i32 {
+    match blast.square_dist(pos) {
+        0 => 2,
+        1 => 1,
+        _ => 0,
+    }
+}
+
+// Local referee, playing the orders of both submarines
+#[derive(Debug, Clone, PartialEq)]
+pub struct SubState {
+    pub pos: Pos,
+    // Islands and visited cells
+    pub forbidden_map: Map,
+    pub life: i32,
+    pub torpedo: usize,
+    pub sonar: usize,
+    pub silence: usize,
+    pub mine: usize,
+    pub mines: Vec<Pos>,
+}
+
+impl SubState {
+    pub fn new(map: &Map, pos: Pos) -> Self {
+        let mut forbidden_map = map.clone();
+        forbidden_map.set(pos.y, pos.x, true);
+        Self {
+            pos,
+            forbidden_map,
+            life: MAX_LIFE,
+            torpedo: cooldown::TORPEDO,
+            sonar: cooldown::SONAR,
+            silence: cooldown::SILENCE,
+            mine: cooldown::MINE,
+            mines: vec![],
+        }
+    }
+
+    pub fn from_me(me: &MePlayer) -> Self {
+        Self {
+            pos: me.pos,
+            forbidden_map: me.forbidden_map.clone(),
+            life: me.life,
+            torpedo: me.torpedo,
+            sonar: me.sonar,
+            silence: me.silence,
+            mine: me.mine,
+            mines: me.mines.clone(),
+        }
+    }
+
+    pub fn cooldown_mut(&mut self, system: &System) -> &mut usize {
+        match system {
+            System::Torpedo => &mut self.torpedo,
+            System::Sonar => &mut self.sonar,
+            System::Silence => &mut self.silence,
+            System::Mine => &mut self.mine,
+        }
+    }
+
+    pub fn step(&mut self, map: &Map, dir: &Direction) -> Result<(), ()> {
+        let next = dir.apply(&self.pos)?;
+        if next.x >= map.w || next.y >= map.h || self.forbidden_map.get(next.y, next.x) {
+            return Err(());
+        }
+        self.pos = next;
+        self.forbidden_map.set(next.y, next.x, true);
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rules {
+    pub map: Map,
+    pub subs: [SubState; 2],
+}
+
+impl Rules {
+    pub fn new(map: Map, subs: [SubState; 2]) -> Self {
+        Self { map, subs }
+    }
+
+    pub fn blast(&mut self, pos: &Pos) {
+        for sub in self.subs.iter_mut() {
+            sub.life -= blast_damage(pos, &sub.pos);
+        }
+    }
+
+    // Orders are played until the first invalid one
+    pub fn play(&mut self, player: usize, actions: &[Action]) -> Result<(), ()> {
+        for action in actions.iter() {
+            self.play_action(player, action)?;
+        }
+        Ok(())
+    }
+
+    pub fn play_action(&mut self, player: usize, action: &Action) -> Result<(), ()> {
+        let map = &self.map;
+        let sub = &mut self.subs[player];
+        match action {
+            Action::Move(dir, system) => {
+                sub.step(map, dir)?;
+                let cooldown = sub.cooldown_mut(system);
+                *cooldown = cooldown.saturating_sub(1);
+            }
+            Action::Surface(_) => {
+                sub.forbidden_map.copy_from(map);
+                sub.forbidden_map.set(sub.pos.y, sub.pos.x, true);
+                sub.life -= 1;
+            }
+            Action::Torpedo(pos) => {
+                if sub.torpedo > 0 || pos.x >= map.w || pos.y >= map.h {
+                    return Err(());
+                }
+                if map.distances_from(&sub.pos, 4)[pos.y * map.w + pos.x].is_none() {
+                    return Err(());
+                }
+                sub.torpedo = cooldown::TORPEDO;
+                self.blast(pos);
+            }
+            Action::Sonar(_) => {
+                if sub.sonar > 0 {
+                    return Err(());
+                }
+                sub.sonar = cooldown::SONAR;
+            }
+            Action::Silence(dir, dist) => {
+                if sub.silence > 0 || *dist > 4 {
+                    return Err(());
+                }
+                for _ in 0..*dist {
+                    sub.step(map, dir)?;
+                }
+                sub.silence = cooldown::SILENCE;
+            }
+            Action::Mine(dir) => {
+                let pos = dir.apply(&sub.pos)?;
+                if sub.mine > 0 || pos.x >= map.w || pos.y >= map.h || map.get(pos.y, pos.x) {
+                    return Err(());
+                }
+                if sub.mines.contains(&pos) {
+                    return Err(());
+                }
+                sub.mines.push(pos);
+                sub.mine = cooldown::MINE;
+            }
+            Action::Trigger(pos) => {
+                let index = match sub.mines.iter().position(|mine| mine == pos) {
+                    Some(index) => index,
+                    None => return Err(()),
+                };
+                sub.mines.remove(index);
+                self.blast(pos);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn is_over(&self) -> bool {
+        self.subs.iter().any(|sub| sub.life <= 0)
+    }
+}
+
+// Tests -----------------------------------------------------------------
+#[test]
+fn test_rules() {
+    let map = Map::new(15, 15);
+    let mut rules = Rules::new(
+        map.clone(),
+        [
+            SubState::new(&map, Pos { y: 7, x: 7 }),
+            SubState::new(&map, Pos { y: 7, x: 9 }),
+        ],
+    );
+    assert_eq!(
+        rules.play(0, &[Action::Move(Direction::E, System::Torpedo)]),
+        Ok(())
+    );
+    // Moving back on our path
+    assert_eq!(
+        rules.play(0, &[Action::Move(Direction::W, System::Torpedo)]),
+        Err(())
+    );
+    rules.subs[0].torpedo = 0;
+    assert_eq!(
+        rules.play(0, &[Action::Torpedo(Pos { y: 7, x: 9 })]),
+        Ok(())
+    );
+    assert_eq!(rules.subs[0].life, MAX_LIFE - 1);
+    assert_eq!(rules.subs[1].life, MAX_LIFE - 2);
+    assert!(!rules.is_over());
+}
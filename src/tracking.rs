@@ -0,0 +1,528 @@
+use crate::geometry::*;
+use crate::protocol::*;
+
+// =======================================================================
+// Game data
+// =======================================================================
+// -----------------------------------------------------------------------
+// PosMap
+// -----------------------------------------------------------------------
+#[derive(Debug, Clone, PartialEq)]
+pub enum FuzzyPos {
+    Exact(Pos),
+    Area(NewMap),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PosData {
+    // Internal context
+    pub water_map: NewMap,
+
+    // Variables
+    pub pos: FuzzyPos,
+
+    // TODO Add forbidden path proccessing
+    pub forbidden_map: NewMap,
+    pub last_moves_since_lost: Vec<Direction>,
+}
+
+impl PosData {
+    pub fn new(water_map: NewMap) -> Self {
+        Self {
+            water_map,
+            pos: FuzzyPos::Area(water_map),
+            forbidden_map: !water_map,
+            last_moves_since_lost: vec![],
+        }
+    }
+
+    pub fn analyse_action(&mut self, action: &OppAction) {
+        unsafe {
+            let mut new_pos = None;
+            match &mut self.pos {
+                FuzzyPos::Area(map) => {
+                    match action {
+                        OppAction::Move(dir) => {
+                            map.shift(dir, 1);
+                            *map &= self.water_map;
+                        }
+                        OppAction::Surface(sector) => {
+                            *map &= SECTOR_MASK[*sector];
+                        }
+                        OppAction::Torpedo(pos) => {
+                            let shift = pos.isub(&TORPEDO_RANGE_MAP.offset);
+                            let mask = TORPEDO_RANGE_MAP.map.clone().ipos_shift(shift);
+                            *map &= mask;
+                        }
+                        OppAction::Silence => {
+                            map.compose(&SILENCE_RANGE_MAP.map, &SILENCE_RANGE_MAP.offset);
+                        }
+                        OppAction::Sonar(_) => (),
+                    }
+                    if map.count(true) == 1 {
+                        new_pos = Some(FuzzyPos::Exact(map.first_match(true).unwrap()));
+                    }
+                }
+                FuzzyPos::Exact(pos) => match action {
+                    OppAction::Move(dir) => {
+                        let new_pos = dir.apply(pos).unwrap();
+                        pos.y = new_pos.y;
+                        pos.x = new_pos.x;
+                    }
+                    OppAction::Surface(_) => (),
+                    OppAction::Torpedo(_) => (),
+                    OppAction::Silence => {
+                        let mut map = SILENCE_RANGE_MAP.map;
+                        map.ipos_shift(pos.isub(&SILENCE_RANGE_MAP.offset));
+                        // TODO Add known forbidden path constraint
+                        map &= self.water_map;
+
+                        new_pos = Some(FuzzyPos::Area(map));
+                    }
+                    OppAction::Sonar(_) => (),
+                },
+            }
+            if let Some(pos) = new_pos {
+                self.pos = pos;
+            }
+        }
+    }
+    pub fn analyse_actions(&mut self, actions: &[OppAction]) {
+        actions
+            .iter()
+            .for_each(|action| self.analyse_action(action));
+    }
+
+    // TODO Add support for self successful torpedo processing
+}
+
+// -----------------------------------------------------------------------
+// MePlayer
+// -----------------------------------------------------------------------
+#[derive(Debug, Clone, PartialEq)]
+pub struct MePlayer {
+    pub forbidden_map: Map,
+    pub pos: Pos,
+    pub life: i32,
+    pub torpedo: usize,
+    pub sonar: usize,
+    pub silence: usize,
+    pub mine: usize,
+    pub mines: Vec<Pos>,
+    // Where the opponent may think we are, from the orders we gave
+    pub seen: OppPos,
+}
+
+impl MePlayer {
+    pub fn new(map: &Map) -> Self {
+        let mut pos_map = map.clone();
+        pos_map.invert();
+        Self {
+            forbidden_map: map.clone(),
+            pos: Pos { y: 0, x: 0 },
+            life: MAX_LIFE,
+            torpedo: cooldown::TORPEDO,
+            sonar: cooldown::SONAR,
+            silence: cooldown::SILENCE,
+            mine: cooldown::MINE,
+            mines: vec![],
+            seen: OppPos::Area(pos_map),
+        }
+    }
+}
+
+// -----------------------------------------------------------------------
+// OppPos
+// -----------------------------------------------------------------------
+#[derive(Debug, Clone, PartialEq)]
+pub enum OppPos {
+    Exact(Pos),
+    Area(Map),
+}
+impl OppPos {
+    // Narrows the position down with one of the orders the tracked player gave
+    pub fn apply(&mut self, action: &Action, islands: &Map) {
+        if let OppPos::Exact(pos) = self {
+            match action {
+                Action::Move(dir, _) => {
+                    *pos = dir.apply(pos).unwrap();
+                    return;
+                }
+                Action::Silence(_, _) => {
+                    let mut map = Map::new(islands.h, islands.w);
+                    map.set(pos.y, pos.x, true);
+                    *self = OppPos::Area(map);
+                }
+                _ => return,
+            }
+        }
+        let mut exact = None;
+        if let OppPos::Area(map) = self {
+            match action {
+                Action::Move(dir, _) => {
+                    map.eq_shift(dir);
+                    map.eq_and_not(islands);
+                }
+                Action::Surface(sector) => {
+                    let mask = map.sector_mask(*sector);
+                    map.eq_and(&mask);
+                }
+                Action::Torpedo(pos) => {
+                    let mask = map.torpedo_mask(pos);
+                    map.eq_and(&mask);
+                }
+                Action::Silence(_, _) => map.silence_expand(islands),
+                Action::Sonar(_) | Action::Mine(_) | Action::Trigger(_) => (),
+            }
+            if map.nb_true() == 1 {
+                exact = map.first_true();
+            }
+        }
+        if let Some(pos) = exact {
+            *self = OppPos::Exact(pos);
+        }
+    }
+
+    pub fn apply_sector_answer(&mut self, islands: &Map, sector: usize, inside: bool) {
+        let mut exact = None;
+        if let OppPos::Area(map) = self {
+            let mask = islands.sector_mask(sector);
+            if inside {
+                map.eq_and(&mask);
+            } else {
+                map.eq_and_not(&mask);
+            }
+            if map.nb_true() == 1 {
+                exact = map.first_true();
+            }
+        }
+        if let Some(pos) = exact {
+            *self = OppPos::Exact(pos);
+        }
+    }
+
+    pub fn candidates(&self, h: usize, w: usize) -> Map {
+        match self {
+            OppPos::Exact(pos) => {
+                let mut ret = Map::new(h, w);
+                ret.set(pos.y, pos.x, true);
+                ret
+            }
+            OppPos::Area(area) => area.clone(),
+        }
+    }
+}
+impl std::fmt::Display for OppPos {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                OppPos::Exact(pos) => format!("Exact: {}", pos),
+                OppPos::Area(map) => format!("Area:\n{}", map),
+            }
+        )
+    }
+}
+
+// -----------------------------------------------------------------------
+// OppMines
+// -----------------------------------------------------------------------
+// Each MINE order puts a mine next to one of the opponent candidate positions
+// of that time, so every mine is a probability spread over the map cells.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OppMines {
+    pub h: usize,
+    pub w: usize,
+    pub mines: Vec<Box<[f32]>>,
+}
+
+impl OppMines {
+    pub fn new(h: usize, w: usize) -> Self {
+        Self {
+            h,
+            w,
+            mines: vec![],
+        }
+    }
+
+    pub fn add(&mut self, candidates: &Map, map: &Map) {
+        let dirs = [Direction::N, Direction::E, Direction::S, Direction::W];
+        let mut mine = vec![0.0; self.h * self.w].into_boxed_slice();
+        let mut nb_candidates = 0;
+        for y in 0..self.h {
+            for x in 0..self.w {
+                if !candidates.get(y, x) {
+                    continue;
+                }
+                let spots: Vec<_> = dirs
+                    .iter()
+                    .filter_map(|dir| dir.apply(&Pos { y, x }).ok())
+                    .filter(|pos| pos.x < self.w && pos.y < self.h && !map.get(pos.y, pos.x))
+                    .collect();
+                if spots.is_empty() {
+                    continue;
+                }
+                nb_candidates += 1;
+                for pos in spots.iter() {
+                    mine[pos.y * self.w + pos.x] += 1.0 / spots.len() as f32;
+                }
+            }
+        }
+        if nb_candidates == 0 {
+            return;
+        }
+        mine.iter_mut().for_each(|p| *p /= nb_candidates as f32);
+        self.mines.push(mine);
+    }
+
+    // The mine that blew up is assumed to be the one most likely at pos
+    pub fn trigger(&mut self, pos: &Pos) {
+        let i = pos.y * self.w + pos.x;
+        let best = self
+            .mines
+            .iter()
+            .enumerate()
+            .max_by(|(_, max), (_, v)| max[i].partial_cmp(&v[i]).unwrap());
+        if let Some((index, _)) = best {
+            self.mines.remove(index);
+        }
+    }
+
+    // Expected number of opponent mines on each cell
+    pub fn danger(&self) -> Vec<f32> {
+        let mut ret = vec![0.0; self.h * self.w];
+        for mine in self.mines.iter() {
+            for (d, p) in ret.iter_mut().zip(mine.iter()) {
+                *d += *p;
+            }
+        }
+        ret
+    }
+
+    // Expected damage taken at pos if every opponent mine went off
+    pub fn blast_danger(&self, danger: &[f32], pos: &Pos) -> f32 {
+        let mut ret = 0.0;
+        for y in pos.y.saturating_sub(1)..std::cmp::min(pos.y + 2, self.h) {
+            for x in pos.x.saturating_sub(1)..std::cmp::min(pos.x + 2, self.w) {
+                let damage = if y == pos.y && x == pos.x { 2.0 } else { 1.0 };
+                ret += damage * danger[y * self.w + x];
+            }
+        }
+        ret
+    }
+}
+
+// -----------------------------------------------------------------------
+// OppProfile
+// -----------------------------------------------------------------------
+// Habits of the opponent over the match. The tracker only knows which cells
+// are possible, the profile tells which of them are likely.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OppProfile {
+    // MOVE orders in each direction, indexed N, E, S, W
+    pub moves: [usize; 4],
+    // Silence distances we could work out once the opponent was located
+    pub silence_dists: [usize; 5],
+    // Sectors the opponent surfaced or was located in
+    pub sectors: [usize; 9],
+    // Turns started with the torpedo likely charged, and torpedoes fired
+    pub torpedo_ready: usize,
+    pub torpedo_fired: usize,
+    // Candidates before the last silence, the moves made since and whether
+    // its distance is known
+    pub silence_from: Option<Map>,
+    pub silence_shift: (isize, isize),
+    pub silence_resolved: bool,
+}
+
+impl OppProfile {
+    pub fn new() -> Self {
+        Self {
+            moves: [0; 4],
+            silence_dists: [0; 5],
+            sectors: [0; 9],
+            torpedo_ready: 0,
+            torpedo_fired: 0,
+            silence_from: None,
+            silence_shift: (0, 0),
+            silence_resolved: true,
+        }
+    }
+
+    pub fn dir_index(dir: &Direction) -> usize {
+        match dir {
+            Direction::N => 0,
+            Direction::E => 1,
+            Direction::S => 2,
+            Direction::W => 3,
+        }
+    }
+
+    pub fn start_turn(&mut self, torpedo_ready: bool) {
+        if torpedo_ready {
+            self.torpedo_ready += 1;
+        }
+    }
+
+    // Called with each opponent order, before the tracker applies it
+    pub fn record(&mut self, action: &Action, candidates: &Map) {
+        match action {
+            Action::Move(dir, _) => {
+                self.moves[Self::dir_index(dir)] += 1;
+                let (dy, dx) = &mut self.silence_shift;
+                match dir {
+                    Direction::N => *dy -= 1,
+                    Direction::E => *dx += 1,
+                    Direction::S => *dy += 1,
+                    Direction::W => *dx -= 1,
+                }
+            }
+            Action::Silence(_, _) => {
+                self.silence_from = Some(candidates.clone());
+                self.silence_shift = (0, 0);
+                self.silence_resolved = false;
+            }
+            Action::Surface(sector) => self.sectors[sector - 1] += 1,
+            Action::Torpedo(_) => self.torpedo_fired += 1,
+            _ => (),
+        }
+    }
+
+    // Distance of a silence from origin that would leave the opponent at pos
+    // after the moves made since
+    pub fn silence_dist(&self, origin: &Pos, pos: &Pos) -> Option<usize> {
+        let (dy, dx) = self.silence_shift;
+        let dy = pos.y as isize - dy - origin.y as isize;
+        let dx = pos.x as isize - dx - origin.x as isize;
+        let dist = (dy.abs() + dx.abs()) as usize;
+        if (dy == 0 || dx == 0) && dist <= 4 {
+            Some(dist)
+        } else {
+            None
+        }
+    }
+
+    // Called at the end of the opponent turn when the tracker knows its
+    // position
+    pub fn locate(&mut self, pos: &Pos, map: &Map) {
+        self.sectors[map.sector(pos) - 1] += 1;
+        if self.silence_resolved {
+            return;
+        }
+        self.silence_resolved = true;
+        let from = match &self.silence_from {
+            Some(from) => from,
+            None => return,
+        };
+        let mut dists: Vec<_> = (0..from.h)
+            .flat_map(|y| (0..from.w).map(move |x| Pos { y, x }))
+            .filter(|origin| from.get(origin.y, origin.x))
+            .filter_map(|origin| self.silence_dist(&origin, pos))
+            .collect();
+        dists.dedup();
+        if dists.len() == 1 {
+            self.silence_dists[dists[0]] += 1;
+        }
+    }
+
+    // Move frequencies, 1 for a direction as frequent as the others
+    pub fn dir_bias(&self) -> [f32; 4] {
+        let total = self.moves.iter().sum::<usize>() as f32 + 4.0;
+        let mut ret = [0.0; 4];
+        for (bias, nb) in ret.iter_mut().zip(self.moves.iter()) {
+            *bias = 4.0 * (*nb as f32 + 1.0) / total;
+        }
+        ret
+    }
+
+    // Share of turns with a charged torpedo it was fired
+    pub fn fire_rate(&self) -> f32 {
+        (self.torpedo_fired as f32 + 1.0) / (self.torpedo_ready as f32 + 2.0)
+    }
+
+    // Weight of each candidate cell, from the sectors the opponent likes and
+    // the distances it usually silences at
+    pub fn weights(&self, candidates: &Map, map: &Map) -> Vec<f32> {
+        let nb_sectors = self.sectors.iter().sum::<usize>() as f32 + 9.0;
+        let nb_silences = self.silence_dists.iter().sum::<usize>() as f32 + 5.0;
+        let from = match &self.silence_from {
+            Some(from) if !self.silence_resolved => Some(from),
+            _ => None,
+        };
+        let mut ret = vec![0.0; candidates.h * candidates.w];
+        for y in 0..candidates.h {
+            for x in 0..candidates.w {
+                if !candidates.get(y, x) {
+                    continue;
+                }
+                let pos = Pos { y, x };
+                let nb = self.sectors[map.sector(&pos) - 1] as f32 + 1.0;
+                let mut weight = 9.0 * nb / nb_sectors;
+                if let Some(from) = from {
+                    let nb = (0..from.h)
+                        .flat_map(|y| (0..from.w).map(move |x| Pos { y, x }))
+                        .filter(|origin| from.get(origin.y, origin.x))
+                        .filter_map(|origin| self.silence_dist(&origin, &pos))
+                        .map(|dist| self.silence_dists[dist])
+                        .max()
+                        .unwrap_or(0);
+                    weight *= 5.0 * (nb as f32 + 1.0) / nb_silences;
+                }
+                ret[y * candidates.w + x] = weight;
+            }
+        }
+        ret
+    }
+}
+
+impl Default for OppProfile {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct OppPlayer {
+    pub life: i32,
+    // TODO All those fields should be fuzzy values
+    pub forbidden_map: Option<Map>,
+    pub pos: OppPos,
+    pub torpedo: usize,
+    pub sonar: usize,
+    pub silence: usize,
+    pub mine: usize,
+    pub mines: OppMines,
+    pub profile: OppProfile,
+    // Torpedo and mine blasts of its last turn
+    pub blasts: Vec<Pos>,
+}
+
+impl OppPlayer {
+    pub fn new(map: &Map) -> Self {
+        let mut pos_map = map.clone();
+        pos_map.invert();
+        Self {
+            forbidden_map: None,
+            pos: OppPos::Area(pos_map),
+            life: MAX_LIFE,
+            torpedo: cooldown::TORPEDO,
+            sonar: cooldown::SONAR,
+            silence: cooldown::SILENCE,
+            mine: cooldown::MINE,
+            mines: OppMines::new(map.h, map.w),
+            profile: OppProfile::new(),
+            blasts: vec![],
+        }
+    }
+
+    // Cells the opponent may currently be in
+    pub fn candidates(&self, map: &Map) -> Map {
+        self.pos.candidates(map.h, map.w)
+    }
+
+    // How likely each cell is, up to a factor
+    pub fn weights(&self, map: &Map) -> Vec<f32> {
+        self.profile.weights(&self.candidates(map), map)
+    }
+}
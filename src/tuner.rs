@@ -0,0 +1,242 @@
+use crate::ai::*;
+use crate::arena::*;
+use crate::bot::*;
+use crate::replay::*;
+use rand::{Rng, SeedableRng};
+
+// =======================================================================
+// Tuner
+// =======================================================================
+// Local search over the AI configuration: each generation mutates the best
+// configuration into a few candidates, which play it on fresh maps with both
+// player ids. The best of several scores is lucky as much as good, so the
+// candidate scoring the most plays it again on other maps, and replaces it
+// when it wins enough of those games too.
+pub const TUNER_CANDIDATES: usize = 4;
+pub const TUNER_MAPS: usize = 8;
+pub const TUNER_CONFIRM_MAPS: usize = 16;
+// Parameters a mutation changes at most, and their relative step
+pub const TUNER_MAX_CHANGES: usize = 3;
+pub const TUNER_STEP: f32 = 0.3;
+// Score against the best configuration a candidate needs, in both rounds, to
+// replace it
+pub const TUNER_ACCEPT_SCORE: f32 = 0.55;
+// Search budgets are left alone: more time always plays better, but the
+// submission does not have it
+pub const TUNER_FIXED: [&str; 6] = [
+    "path_search_depth",
+    "path_search_budget",
+    "silence_search_budget",
+    "search_max_plies",
+    "search_samples_per_depth",
+    "search_time_share",
+];
+
+pub struct Generation {
+    pub score: f32,
+    // Score of the second round, when the first one was good enough
+    pub confirmation: Option<f32>,
+    pub accepted: bool,
+    // Parameters of the best candidate, with their new value
+    pub changes: Vec<(String, f32)>,
+}
+
+pub struct Tuner {
+    pub best: AiConfig,
+    pub seed: u64,
+    pub generation: usize,
+    pub nb_candidates: usize,
+    pub nb_maps: usize,
+    pub nb_confirm_maps: usize,
+    // Games played so far, each one getting its own seeds
+    pub nb_games: usize,
+    pub max_turns: usize,
+    pub threads: usize,
+    pub rng: rand::rngs::StdRng,
+}
+
+impl Tuner {
+    pub fn new(best: AiConfig, seed: u64) -> Self {
+        Self {
+            best,
+            seed,
+            generation: 0,
+            nb_candidates: TUNER_CANDIDATES,
+            nb_maps: TUNER_MAPS,
+            nb_confirm_maps: TUNER_CONFIRM_MAPS,
+            nb_games: 0,
+            max_turns: crate::protocol::MAX_TURNS,
+            threads: 1,
+            rng: rand::rngs::StdRng::seed_from_u64(seed),
+        }
+    }
+
+    // Moves a few parameters by up to TUNER_STEP of their value, or of 1 when
+    // smaller, within their range. Integer ones move by one at least.
+    pub fn mutate(&mut self) -> (AiConfig, Vec<(String, f32)>) {
+        let params: Vec<_> = self
+            .best
+            .values()
+            .into_iter()
+            .filter(|(key, _)| !TUNER_FIXED.contains(&key.as_str()))
+            .collect();
+        let mut config = self.best.clone();
+        let mut changes = vec![];
+        for _ in 0..self.rng.gen_range(1, TUNER_MAX_CHANGES + 1) {
+            let (key, value) = &params[self.rng.gen_range(0, params.len())];
+            let (min, max) = AiConfig::range(key).unwrap();
+            let step = TUNER_STEP * value.abs().max(1.0) * self.rng.gen_range(-1.0, 1.0);
+            let new_value = (value + step).max(min).min(max);
+            if config.set(key, new_value).is_err() {
+                let mut rounded = new_value.round();
+                if rounded == *value {
+                    let up = rounded < max && (self.rng.gen() || rounded <= min);
+                    rounded += if up { 1.0 } else { -1.0 };
+                }
+                config.set(key, rounded).unwrap();
+            }
+            changes.retain(|(changed, _)| changed != key);
+            let new_value = config
+                .values()
+                .into_iter()
+                .find(|(k, _)| k == key)
+                .unwrap()
+                .1;
+            changes.push((key.clone(), new_value));
+        }
+        (config, changes)
+    }
+
+    // Score of each candidate against the best configuration on nb_maps new
+    // maps, 1 for a win and 0.5 for a draw
+    pub fn evaluate(&mut self, candidates: &[AiConfig], nb_maps: usize) -> Vec<f32> {
+        let first = self.nb_games;
+        let maps = map_pool(self.seed.wrapping_add(first as u64), nb_maps);
+        let games_per_candidate = 2 * maps.len();
+        self.nb_games += candidates.len() * games_per_candidate;
+        let tuner = &*self;
+        let scores = run_parallel(candidates.len() * games_per_candidate, self.threads, |i| {
+            let candidate = &candidates[i / games_per_candidate];
+            let game = i % games_per_candidate;
+            let seed = tuner.seed.wrapping_add(2 * (first + i) as u64);
+            let mut configs = [candidate.clone(), tuner.best.clone()];
+            // Candidate as player 1 on odd games
+            configs.swap(0, game % 2);
+            let mut bot0 = Ai::with_config(seed, configs[0].clone());
+            let mut bot1 = Ai::with_config(seed + 1, configs[1].clone());
+            let bots: [&mut dyn Bot; 2] = [&mut bot0, &mut bot1];
+            match play_bots(&maps[game / 2], bots, tuner.max_turns).1 {
+                Some(player) if player == game % 2 => 1.0,
+                Some(_) => 0.0,
+                None => 0.5,
+            }
+        });
+        scores
+            .chunks(games_per_candidate)
+            .map(|chunk| chunk.iter().sum::<f32>() / games_per_candidate as f32)
+            .collect()
+    }
+
+    pub fn step(&mut self) -> Generation {
+        let (candidates, changes): (Vec<_>, Vec<_>) =
+            (0..self.nb_candidates).map(|_| self.mutate()).unzip();
+        self.select(&candidates, &changes)
+    }
+
+    // Plays the candidates, then the best of them again, and keeps it when it
+    // wins both rounds
+    pub fn select(
+        &mut self,
+        candidates: &[AiConfig],
+        changes: &[Vec<(String, f32)>],
+    ) -> Generation {
+        let scores = self.evaluate(candidates, self.nb_maps);
+        self.generation += 1;
+        let best = (0..scores.len())
+            .max_by(|a, b| scores[*a].partial_cmp(&scores[*b]).unwrap())
+            .unwrap();
+        let confirmation = match scores[best] >= TUNER_ACCEPT_SCORE {
+            true => Some(self.evaluate(&candidates[best..=best], self.nb_confirm_maps)[0]),
+            false => None,
+        };
+        let accepted = matches!(confirmation, Some(score) if score >= TUNER_ACCEPT_SCORE);
+        if accepted {
+            self.best = candidates[best].clone();
+        }
+        Generation {
+            score: scores[best],
+            confirmation,
+            accepted,
+            changes: changes[best].clone(),
+        }
+    }
+}
+
+// Tests -----------------------------------------------------------------
+#[test]
+fn test_tuner() {
+    let mut tuner = Tuner::new(AiConfig::new(), 1);
+    for _ in 0..20 {
+        let (config, changes) = tuner.mutate();
+        assert!(!changes.is_empty() && changes.len() <= TUNER_MAX_CHANGES);
+        for (key, value) in config.values() {
+            let old = tuner
+                .best
+                .values()
+                .into_iter()
+                .find(|(k, _)| *k == key)
+                .unwrap()
+                .1;
+            match changes.iter().find(|(changed, _)| *changed == key) {
+                Some((_, new)) => assert_eq!(value, *new),
+                None => assert_eq!(value, old),
+            }
+            if TUNER_FIXED.contains(&key.as_str()) {
+                assert_eq!(value, old);
+            }
+            let (min, max) = AiConfig::range(&key).unwrap();
+            assert!((min..=max).contains(&value));
+        }
+        assert_eq!(AiConfig::parse(&config.to_string()), Ok(config));
+    }
+
+    tuner.nb_candidates = 2;
+    tuner.nb_maps = 1;
+    tuner.nb_confirm_maps = 1;
+    tuner.max_turns = 2;
+    tuner.threads = 2;
+    let best = tuner.best.clone();
+    let generation = tuner.step();
+    assert_eq!(tuner.generation, 1);
+    assert!((0.0..=1.0).contains(&generation.score));
+    assert_eq!(generation.accepted, tuner.best != best);
+    assert_eq!(
+        generation.confirmation.is_some(),
+        generation.score >= TUNER_ACCEPT_SCORE
+    );
+    assert_eq!(
+        tuner.nb_games,
+        2 * 2 + generation.confirmation.map_or(0, |_| 2)
+    );
+}
+
+#[test]
+fn test_tuner_replaces_worse() {
+    // Searches cut short to keep the games fast
+    let fast = AiConfig::parse("search_max_plies = 0\npath_search_budget = 500").unwrap();
+    // Every route looks short, so that this one surfaces every turn
+    let mut worse = fast.clone();
+    worse.set("path_search_depth", 1.0).unwrap();
+    worse.set("surface_cheap_gain", 1.0).unwrap();
+    worse.set("surface_min_life", 1.0).unwrap();
+    let mut tuner = Tuner::new(worse, 1);
+    tuner.nb_maps = 1;
+    tuner.nb_confirm_maps = 2;
+    tuner.max_turns = 10;
+    tuner.threads = 2;
+    let generation = tuner.select(std::slice::from_ref(&fast), &[vec![]]);
+    assert_eq!(generation.score, 1.0);
+    assert_eq!(generation.confirmation, Some(1.0));
+    assert!(generation.accepted);
+    assert_eq!(tuner.best, fast);
+}
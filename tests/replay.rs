@@ -0,0 +1,16 @@
+use ocean_of_code::geometry::Map;
+use ocean_of_code::replay::*;
+use rand::SeedableRng;
+
+#[test]
+fn test_replayed_match() {
+    let mut rng = rand::rngs::StdRng::seed_from_u64(3);
+    let map = Map::random(9, 9, MATCH_ISLAND_RATIO, &mut rng);
+    let replay = play_match(&map, [3, 4], 4);
+
+    // Every recorded order is accepted again
+    let frames = replay.frames().unwrap();
+    assert_eq!(frames.len(), replay.turns.len());
+    let loaded = Replay::parse(&replay.to_string()).unwrap();
+    assert_eq!(loaded.frames().unwrap().len(), frames.len());
+}